@@ -4,7 +4,11 @@ use std::path::Path;
 use anyhow::{Context, Result};
 use rustypipe::{
     client::RustyPipe,
-    model::{PlaylistItem, VideoItem, YouTubeItem},
+    model::{
+        AlbumItem, AlbumType, ArtistId, ArtistItem, ChannelItem, MusicItem, MusicPlaylistItem,
+        PlaylistItem, Thumbnail, TrackItem, VideoItem, YouTubeItem,
+    },
+    param::search_filter::{ItemType, Length, MusicSearchFilter, SearchFilter, UploadDate},
 };
 
 use crate::cache::Cache;
@@ -15,21 +19,165 @@ pub struct SearchItem {
     pub snippet: Snippet,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct ItemId {
     pub kind: String,
     #[serde(rename = "videoId")]
     pub video_id: Option<String>,
     #[serde(rename = "playlistId")]
     pub playlist_id: Option<String>,
+    /// YouTube Music album browse id (`MPREb_...`)
+    #[serde(default, rename = "albumId")]
+    pub album_id: Option<String>,
+    /// YouTube Music artist channel id
+    #[serde(default, rename = "artistId")]
+    pub artist_id: Option<String>,
+    #[serde(default, rename = "channelId")]
+    pub channel_id: Option<String>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct Snippet {
     pub title: String,
     #[serde(rename = "channelTitle")]
     pub channel_title: String,
     pub description: Option<String>,
+    // Fields below were added after the first cache format; default them so old entries load
+    /// Length in seconds, `None` for livestreams and playlists
+    #[serde(default)]
+    pub duration: Option<u32>,
+    #[serde(default, rename = "viewCount")]
+    pub view_count: Option<u64>,
+    /// Publish date as `YYYY-MM-DD`, or YouTube's relative text (e.g. `3 weeks ago`)
+    #[serde(default, rename = "publishedAt")]
+    pub published_at: Option<String>,
+    /// URL of the largest thumbnail
+    #[serde(default, rename = "thumbnailUrl")]
+    pub thumbnail_url: Option<String>,
+    #[serde(default, rename = "isLive")]
+    pub is_live: bool,
+    #[serde(default, rename = "isShort")]
+    pub is_short: bool,
+}
+
+/// Kind of result to search for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ItemKind {
+    Video,
+    Playlist,
+    Channel,
+    /// YouTube Music song (implies --music)
+    Song,
+    /// YouTube Music album (implies --music)
+    Album,
+    /// YouTube Music artist (implies --music)
+    Artist,
+}
+
+/// Video length to search for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LengthFilter {
+    /// Under 4 minutes
+    Short,
+    /// 4 to 20 minutes
+    Medium,
+    /// Over 20 minutes
+    Long,
+}
+
+/// Upload date range to search in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Since {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+/// Filters applied to [`YouTubeClient::search`]. Type, length and upload date are
+/// applied by YouTube; Shorts and livestreams are dropped from the results afterwards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilters {
+    pub item_type: Option<ItemKind>,
+    pub length: Option<LengthFilter>,
+    pub since: Option<Since>,
+    pub no_shorts: bool,
+    pub no_live: bool,
+    /// Search YouTube Music instead of YouTube
+    pub music: bool,
+}
+
+impl SearchFilters {
+    /// Whether the YouTube Music search is used, either asked for or implied by the type
+    pub fn is_music(&self) -> bool {
+        self.music
+            || matches!(
+                self.item_type,
+                Some(ItemKind::Song | ItemKind::Album | ItemKind::Artist)
+            )
+    }
+
+    /// Whether YouTube has to be asked for filtered results
+    fn has_server_filters(&self) -> bool {
+        self.item_type.is_some() || self.length.is_some() || self.since.is_some()
+    }
+
+    fn to_rustypipe(&self) -> SearchFilter {
+        SearchFilter::new()
+            .item_type_opt(self.item_type.and_then(|kind| match kind {
+                ItemKind::Video => Some(ItemType::Video),
+                ItemKind::Playlist => Some(ItemType::Playlist),
+                ItemKind::Channel => Some(ItemType::Channel),
+                ItemKind::Song | ItemKind::Album | ItemKind::Artist => None,
+            }))
+            .length_opt(self.length.map(|length| match length {
+                LengthFilter::Short => Length::Short,
+                LengthFilter::Medium => Length::Medium,
+                LengthFilter::Long => Length::Long,
+            }))
+            .date_opt(self.since.map(|since| match since {
+                Since::Hour => UploadDate::Hour,
+                Since::Day => UploadDate::Day,
+                Since::Week => UploadDate::Week,
+                Since::Month => UploadDate::Month,
+                Since::Year => UploadDate::Year,
+            }))
+    }
+
+    /// YouTube Music result type to search for; `None` searches all types
+    fn to_music_filter(&self) -> Option<MusicSearchFilter> {
+        self.item_type.map(|kind| match kind {
+            ItemKind::Video => MusicSearchFilter::Videos,
+            ItemKind::Playlist => MusicSearchFilter::CommunityPlaylists,
+            ItemKind::Channel => MusicSearchFilter::Artists,
+            ItemKind::Song => MusicSearchFilter::Tracks,
+            ItemKind::Album => MusicSearchFilter::Albums,
+            ItemKind::Artist => MusicSearchFilter::Artists,
+        })
+    }
+
+    /// Stable description of the filters, used as part of the cache key
+    fn cache_key(&self) -> String {
+        format!(
+            "{:?}:{:?}:{:?}:{}:{}",
+            self.item_type, self.length, self.since, self.no_shorts, self.no_live
+        )
+    }
+
+    fn keeps(&self, item: &SearchItem) -> bool {
+        !(self.no_shorts && item.snippet.is_short || self.no_live && item.snippet.is_live)
+    }
+}
+
+/// Playlists are expanded up to this many tracks
+const MAX_PLAYLIST_TRACKS: usize = 500;
+
+/// Named list of tracks: a playlist, an album or an artist's songs
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TrackList {
+    pub title: String,
+    pub items: Vec<SearchItem>,
 }
 
 pub struct YouTubeClient {
@@ -52,19 +200,39 @@ impl YouTubeClient {
         Ok(Self { pipe, cache })
     }
 
-    pub async fn search(&self, query: &str, max_results: Option<u32>) -> Result<Vec<SearchItem>> {
+    pub async fn search(
+        &self,
+        query: &str,
+        max_results: Option<u32>,
+        filters: &SearchFilters,
+    ) -> Result<Vec<SearchItem>> {
+        if filters.is_music() {
+            return self.music_search(query, max_results, filters).await;
+        }
+
         let max_results = max_results.unwrap_or(5).clamp(1, 50) as usize;
-        let cache_key = format!("ytm::search::{max_results}::{query}");
+        let cache_key = if *filters == SearchFilters::default() {
+            format!("ytm::search::{max_results}::{query}")
+        } else {
+            format!(
+                "ytm::search::{max_results}::{}::{query}",
+                filters.cache_key()
+            )
+        };
 
         if let Some(cached) = self.cache.get::<Vec<SearchItem>>(&cache_key) {
             return Ok(cached);
         }
 
         let pipe_query = self.pipe.query();
-        let search_result = pipe_query
-            .search::<YouTubeItem, _>(query)
-            .await
-            .context("rustypipe search request failed")?;
+        let search_result = if filters.has_server_filters() {
+            pipe_query
+                .search_filter::<YouTubeItem, _>(query, &filters.to_rustypipe())
+                .await
+        } else {
+            pipe_query.search::<YouTubeItem, _>(query).await
+        }
+        .context("rustypipe search request failed")?;
 
         let mut paginator = search_result.items;
         if paginator.items.len() < max_results {
@@ -78,6 +246,7 @@ impl YouTubeClient {
             .items
             .into_iter()
             .filter_map(|item| SearchItem::try_from(item).ok())
+            .filter(|item| filters.keeps(item))
             .take(max_results)
             .collect();
 
@@ -90,6 +259,237 @@ impl YouTubeClient {
 
         Ok(items)
     }
+
+    /// Search YouTube Music for songs, albums, artists and community playlists
+    async fn music_search(
+        &self,
+        query: &str,
+        max_results: Option<u32>,
+        filters: &SearchFilters,
+    ) -> Result<Vec<SearchItem>> {
+        // YouTube Music has no length or date filters, and its items never say they are live or Shorts
+        if filters.length.is_some() || filters.since.is_some() {
+            return Err(anyhow::anyhow!(
+                "--duration and --since are not supported with --music"
+            ));
+        }
+        if filters.no_shorts || filters.no_live {
+            return Err(anyhow::anyhow!(
+                "--no-shorts and --no-live are not supported with --music"
+            ));
+        }
+
+        let max_results = max_results.unwrap_or(5).clamp(1, 50) as usize;
+        let cache_key = format!(
+            "ytm::music_search::{max_results}::{:?}::{query}",
+            filters.item_type
+        );
+        if let Some(cached) = self.cache.get::<Vec<SearchItem>>(&cache_key) {
+            return Ok(cached);
+        }
+
+        let pipe_query = self.pipe.query();
+        let search_result = pipe_query
+            .music_search::<MusicItem, _>(query, filters.to_music_filter())
+            .await
+            .context("rustypipe music search request failed")?;
+
+        let mut paginator = search_result.items;
+        if paginator.items.len() < max_results {
+            paginator
+                .extend_limit(pipe_query.clone(), max_results)
+                .await
+                .context("failed to extend music search results")?;
+        }
+
+        let items: Vec<SearchItem> = paginator
+            .items
+            .into_iter()
+            .filter_map(|item| SearchItem::try_from(item).ok())
+            .take(max_results)
+            .collect();
+
+        if !items.is_empty() {
+            self.cache
+                .put(&cache_key, &items)
+                .context("failed to cache music search results")?;
+        }
+
+        Ok(items)
+    }
+
+    /// A channel's latest uploads and its playlists, led by a playlist of all its uploads
+    pub async fn channel_contents(&self, channel_id: &str) -> Result<Vec<SearchItem>> {
+        let cache_key = format!("ytm::channel::{channel_id}");
+        if let Some(cached) = self.cache.get::<Vec<SearchItem>>(&cache_key) {
+            return Ok(cached);
+        }
+
+        let pipe_query = self.pipe.query();
+        let videos = pipe_query
+            .channel_videos(channel_id)
+            .await
+            .with_context(|| format!("failed to fetch uploads of channel {channel_id}"))?;
+        let playlists = pipe_query
+            .channel_playlists(channel_id)
+            .await
+            .with_context(|| format!("failed to fetch playlists of channel {channel_id}"))?;
+
+        let mut items = Vec::new();
+        if let Some(uploads_id) = uploads_playlist_id(channel_id) {
+            items.push(SearchItem {
+                id: ItemId {
+                    kind: "youtube#playlist".to_string(),
+                    playlist_id: Some(uploads_id),
+                    ..Default::default()
+                },
+                snippet: Snippet {
+                    title: "Play all uploads".to_string(),
+                    channel_title: videos.name.clone(),
+                    description: videos.video_count.map(|count| format!("{} videos", count)),
+                    ..Default::default()
+                },
+            });
+        }
+        let lead = items.len();
+        items.extend(videos.content.items.into_iter().map(SearchItem::from));
+        items.extend(playlists.content.items.into_iter().map(SearchItem::from));
+
+        if items.len() > lead {
+            // Like searches, so an empty reply doesn't hide the channel until the cache expires
+            self.cache
+                .put(&cache_key, &items)
+                .context("failed to cache channel contents")?;
+        }
+        Ok(items)
+    }
+
+    /// Videos of a playlist, in playlist order
+    pub async fn playlist_tracks(&self, playlist_id: &str) -> Result<TrackList> {
+        let cache_key = format!("ytm::playlist::{playlist_id}");
+        if let Some(cached) = self.cache.get::<TrackList>(&cache_key) {
+            return Ok(cached);
+        }
+
+        let pipe_query = self.pipe.query();
+        let playlist = pipe_query
+            .playlist(playlist_id)
+            .await
+            .with_context(|| format!("failed to fetch playlist {playlist_id}"))?;
+        let mut videos = playlist.videos;
+        videos
+            .extend_limit(pipe_query.clone(), MAX_PLAYLIST_TRACKS)
+            .await
+            .with_context(|| format!("failed to fetch videos of playlist {playlist_id}"))?;
+
+        let list = TrackList {
+            title: playlist.name,
+            items: videos
+                .items
+                .into_iter()
+                .take(MAX_PLAYLIST_TRACKS)
+                .map(SearchItem::from)
+                .collect(),
+        };
+        self.cache_track_list(&cache_key, &list)?;
+        Ok(list)
+    }
+
+    /// Tracks of a YouTube Music album, in album order
+    pub async fn music_album_tracks(&self, album_id: &str) -> Result<TrackList> {
+        let cache_key = format!("ytm::music_album::{album_id}");
+        if let Some(cached) = self.cache.get::<TrackList>(&cache_key) {
+            return Ok(cached);
+        }
+
+        let album = self
+            .pipe
+            .query()
+            .music_album(album_id)
+            .await
+            .with_context(|| format!("failed to fetch album {album_id}"))?;
+        let list = TrackList {
+            title: album.name,
+            items: album.tracks.into_iter().map(SearchItem::from).collect(),
+        };
+        self.cache_track_list(&cache_key, &list)?;
+        Ok(list)
+    }
+
+    /// Songs of a YouTube Music artist: the full songs playlist where the artist has one,
+    /// otherwise their top tracks
+    pub async fn music_artist_tracks(&self, artist_id: &str) -> Result<TrackList> {
+        let cache_key = format!("ytm::music_artist::{artist_id}");
+        if let Some(cached) = self.cache.get::<TrackList>(&cache_key) {
+            return Ok(cached);
+        }
+
+        let pipe_query = self.pipe.query();
+        let artist = pipe_query
+            .music_artist(artist_id, false)
+            .await
+            .with_context(|| format!("failed to fetch artist {artist_id}"))?;
+        let tracks = match &artist.tracks_playlist_id {
+            Some(playlist_id) => {
+                pipe_query
+                    .music_playlist(playlist_id)
+                    .await
+                    .with_context(|| format!("failed to fetch songs of artist {artist_id}"))?
+                    .tracks
+                    .items
+            }
+            None => artist.tracks,
+        };
+        let list = TrackList {
+            title: artist.name,
+            items: tracks.into_iter().map(SearchItem::from).collect(),
+        };
+        self.cache_track_list(&cache_key, &list)?;
+        Ok(list)
+    }
+
+    /// Cache a track list unless it came back empty
+    fn cache_track_list(&self, cache_key: &str, list: &TrackList) -> Result<()> {
+        if list.items.is_empty() {
+            return Ok(());
+        }
+        self.cache
+            .put(cache_key, list)
+            .context("failed to cache track list")
+    }
+}
+
+/// Id of the auto-generated playlist holding all uploads of a `UC...` channel
+fn uploads_playlist_id(channel_id: &str) -> Option<String> {
+    channel_id
+        .strip_prefix("UC")
+        .map(|rest| format!("UU{rest}"))
+}
+
+/// Extract the video id from a `watch?v=`, `youtu.be/` or `shorts/` URL
+pub fn video_id_from_url(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let (host, path) = rest.split_once('/')?;
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+
+    let id = match host {
+        "youtu.be" => path.split(['?', '&', '#']).next(),
+        "youtube.com" | "music.youtube.com" => {
+            if let Some(short) = path.strip_prefix("shorts/") {
+                short.split(['?', '&', '#']).next()
+            } else {
+                let query = path.strip_prefix("watch?")?;
+                query
+                    .split(['&', '#'])
+                    .find_map(|pair| pair.strip_prefix("v="))
+            }
+        }
+        _ => None,
+    }?;
+
+    (!id.is_empty()).then(|| id.to_string())
 }
 
 impl TryFrom<YouTubeItem> for SearchItem {
@@ -99,11 +499,33 @@ impl TryFrom<YouTubeItem> for SearchItem {
         match value {
             YouTubeItem::Video(video) => Ok(video.into()),
             YouTubeItem::Playlist(playlist) => Ok(playlist.into()),
-            YouTubeItem::Channel(_) => Err(()),
+            YouTubeItem::Channel(channel) => Ok(channel.into()),
+        }
+    }
+}
+
+impl TryFrom<MusicItem> for SearchItem {
+    type Error = ();
+
+    fn try_from(value: MusicItem) -> Result<Self, Self::Error> {
+        match value {
+            MusicItem::Track(track) => Ok(track.into()),
+            MusicItem::Album(album) => Ok(album.into()),
+            MusicItem::Artist(artist) => Ok(artist.into()),
+            MusicItem::Playlist(playlist) => Ok(playlist.into()),
+            MusicItem::User(_) => Err(()),
         }
     }
 }
 
+/// URL of the widest thumbnail
+fn largest_thumbnail(thumbnails: &[Thumbnail]) -> Option<String> {
+    thumbnails
+        .iter()
+        .max_by_key(|thumbnail| thumbnail.width)
+        .map(|thumbnail| thumbnail.url.clone())
+}
+
 impl From<VideoItem> for SearchItem {
     fn from(video: VideoItem) -> Self {
         let channel_title = video
@@ -111,16 +533,27 @@ impl From<VideoItem> for SearchItem {
             .map(|channel| channel.name)
             .unwrap_or_else(|| "Unknown channel".to_string());
 
+        let published_at = video
+            .publish_date
+            .map(|date| date.date().to_string())
+            .or(video.publish_date_txt);
+
         SearchItem {
             id: ItemId {
                 kind: "youtube#video".to_string(),
                 video_id: Some(video.id),
-                playlist_id: None,
+                ..Default::default()
             },
             snippet: Snippet {
                 title: video.name,
                 channel_title,
                 description: video.short_description,
+                duration: video.duration,
+                view_count: video.view_count,
+                published_at,
+                thumbnail_url: largest_thumbnail(&video.thumbnail),
+                is_live: video.is_live,
+                is_short: video.is_short,
             },
         }
     }
@@ -140,13 +573,160 @@ impl From<PlaylistItem> for SearchItem {
         SearchItem {
             id: ItemId {
                 kind: "youtube#playlist".to_string(),
-                video_id: None,
                 playlist_id: Some(playlist.id),
+                ..Default::default()
             },
             snippet: Snippet {
                 title: playlist.name,
                 channel_title,
                 description,
+                duration: None,
+                view_count: None,
+                published_at: None,
+                thumbnail_url: largest_thumbnail(&playlist.thumbnail),
+                is_live: false,
+                is_short: false,
+            },
+        }
+    }
+}
+
+impl From<ChannelItem> for SearchItem {
+    fn from(channel: ChannelItem) -> Self {
+        let description = match (
+            channel.short_description.is_empty(),
+            channel.subscriber_count,
+        ) {
+            (false, _) => Some(channel.short_description),
+            (true, Some(count)) => Some(format!("{} subscribers", count)),
+            (true, None) => None,
+        };
+
+        SearchItem {
+            id: ItemId {
+                kind: "youtube#channel".to_string(),
+                channel_id: Some(channel.id),
+                ..Default::default()
+            },
+            snippet: Snippet {
+                channel_title: channel.name.clone(),
+                title: channel.name,
+                description,
+                thumbnail_url: largest_thumbnail(&channel.avatar),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Artist names joined for display
+fn artist_names(artists: &[ArtistId]) -> String {
+    if artists.is_empty() {
+        return "Unknown artist".to_string();
+    }
+    artists
+        .iter()
+        .map(|artist| artist.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl From<TrackItem> for SearchItem {
+    fn from(track: TrackItem) -> Self {
+        SearchItem {
+            id: ItemId {
+                kind: "ytmusic#track".to_string(),
+                video_id: Some(track.id),
+                ..Default::default()
+            },
+            snippet: Snippet {
+                title: track.name,
+                channel_title: artist_names(&track.artists),
+                description: track.album.map(|album| album.name),
+                duration: track.duration,
+                view_count: track.view_count,
+                thumbnail_url: largest_thumbnail(&track.cover),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<AlbumItem> for SearchItem {
+    fn from(album: AlbumItem) -> Self {
+        let album_type = match album.album_type {
+            AlbumType::Ep => "EP",
+            AlbumType::Single => "Single",
+            AlbumType::Audiobook => "Audiobook",
+            AlbumType::Show => "Show",
+            _ => "Album",
+        };
+        let description = match album.year {
+            Some(year) => format!("{album_type} · {year}"),
+            None => album_type.to_string(),
+        };
+
+        SearchItem {
+            id: ItemId {
+                kind: "ytmusic#album".to_string(),
+                album_id: Some(album.id),
+                ..Default::default()
+            },
+            snippet: Snippet {
+                title: album.name,
+                channel_title: artist_names(&album.artists),
+                description: Some(description),
+                published_at: album.year.map(|year| year.to_string()),
+                thumbnail_url: largest_thumbnail(&album.cover),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<ArtistItem> for SearchItem {
+    fn from(artist: ArtistItem) -> Self {
+        SearchItem {
+            id: ItemId {
+                kind: "ytmusic#artist".to_string(),
+                artist_id: Some(artist.id),
+                ..Default::default()
+            },
+            snippet: Snippet {
+                channel_title: artist.name.clone(),
+                title: artist.name,
+                description: artist
+                    .subscriber_count
+                    .map(|count| format!("{} subscribers", count)),
+                thumbnail_url: largest_thumbnail(&artist.avatar),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl From<MusicPlaylistItem> for SearchItem {
+    fn from(playlist: MusicPlaylistItem) -> Self {
+        let channel_title = match playlist.channel {
+            Some(channel) => channel.name,
+            None if playlist.from_ytm => "YouTube Music".to_string(),
+            None => "Unknown channel".to_string(),
+        };
+
+        SearchItem {
+            id: ItemId {
+                kind: "ytmusic#playlist".to_string(),
+                playlist_id: Some(playlist.id),
+                ..Default::default()
+            },
+            snippet: Snippet {
+                title: playlist.name,
+                channel_title,
+                description: playlist
+                    .track_count
+                    .map(|count| format!("{} tracks", count)),
+                thumbnail_url: largest_thumbnail(&playlist.thumbnail),
+                ..Default::default()
             },
         }
     }
@@ -194,6 +774,27 @@ mod tests {
         assert_eq!(mapped.snippet.title, "Fantastic Track");
         assert_eq!(mapped.snippet.channel_title, "Great Artist");
         assert_eq!(mapped.snippet.description.as_deref(), Some("A lovely song"));
+        assert_eq!(mapped.snippet.duration, Some(240));
+        assert_eq!(mapped.snippet.view_count, Some(1000));
+        assert_eq!(
+            mapped.snippet.thumbnail_url.as_deref(),
+            Some("http://example.com/thumb.jpg")
+        );
+        assert!(!mapped.snippet.is_live);
+        assert!(!mapped.snippet.is_short);
+    }
+
+    #[test]
+    fn loads_items_cached_before_extra_fields() {
+        let cached = json!({
+            "id": {"kind": "youtube#video", "videoId": "old123", "playlistId": null},
+            "snippet": {"title": "Old Track", "channelTitle": "Old Artist", "description": null}
+        });
+
+        let item: SearchItem = serde_json::from_value(cached).unwrap();
+        assert_eq!(item.snippet.title, "Old Track");
+        assert_eq!(item.snippet.duration, None);
+        assert!(!item.snippet.is_short);
     }
 
     #[test]
@@ -222,9 +823,102 @@ mod tests {
     }
 
     #[test]
-    fn drops_channel_items() {
+    fn maps_music_items() {
+        let track: TrackItem = serde_json::from_value(json!({
+            "id": "track123",
+            "name": "Roads",
+            "duration": 305,
+            "cover": [],
+            "artists": [{"id": "artist1", "name": "Portishead"}],
+            "artist_id": "artist1",
+            "album": {"id": "MPREb_dummy", "name": "Dummy"},
+            "view_count": null,
+            "track_type": "track",
+            "track_nr": 11,
+            "by_va": false
+        }))
+        .unwrap();
+        let mapped: SearchItem = track.into();
+        assert_eq!(mapped.id.kind, "ytmusic#track");
+        assert_eq!(mapped.id.video_id.as_deref(), Some("track123"));
+        assert_eq!(mapped.snippet.channel_title, "Portishead");
+        assert_eq!(mapped.snippet.description.as_deref(), Some("Dummy"));
+        assert_eq!(mapped.snippet.duration, Some(305));
+
+        let album: AlbumItem = serde_json::from_value(json!({
+            "id": "MPREb_dummy",
+            "name": "Dummy",
+            "cover": [],
+            "artists": [{"id": "artist1", "name": "Portishead"}],
+            "artist_id": "artist1",
+            "album_type": "album",
+            "year": 1994,
+            "by_va": false
+        }))
+        .unwrap();
+        let mapped = SearchItem::try_from(MusicItem::Album(album)).unwrap();
+        assert_eq!(mapped.id.album_id.as_deref(), Some("MPREb_dummy"));
+        assert_eq!(mapped.id.video_id, None);
+        assert_eq!(mapped.snippet.description.as_deref(), Some("Album · 1994"));
+
+        let filters = SearchFilters {
+            item_type: Some(ItemKind::Album),
+            ..Default::default()
+        };
+        assert!(filters.is_music());
+        assert!(!SearchFilters::default().is_music());
+    }
+
+    #[test]
+    fn extracts_video_ids_from_urls() {
+        assert_eq!(
+            video_id_from_url("https://www.youtube.com/watch?v=OH4lS6Aem08").as_deref(),
+            Some("OH4lS6Aem08")
+        );
+        assert_eq!(
+            video_id_from_url("https://music.youtube.com/watch?list=RD1&v=abc123&t=10").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            video_id_from_url("https://youtu.be/xyz789?t=42").as_deref(),
+            Some("xyz789")
+        );
+        assert_eq!(
+            video_id_from_url("https://www.youtube.com/playlist?list=PL123"),
+            None
+        );
+        assert_eq!(video_id_from_url("/home/me/song.mp3"), None);
+    }
+
+    #[test]
+    fn filters_shorts_and_livestreams() {
+        let item = |is_short, is_live| SearchItem {
+            id: ItemId::default(),
+            snippet: Snippet {
+                is_short,
+                is_live,
+                ..Default::default()
+            },
+        };
+        let filters = SearchFilters {
+            no_shorts: true,
+            ..Default::default()
+        };
+
+        assert!(filters.keeps(&item(false, false)));
+        assert!(filters.keeps(&item(false, true)));
+        assert!(!filters.keeps(&item(true, false)));
+        assert!(SearchFilters::default().keeps(&item(true, true)));
+
+        let mut filtered = filters.clone();
+        filtered.length = Some(LengthFilter::Short);
+        assert_ne!(filters.cache_key(), filtered.cache_key());
+    }
+
+    #[test]
+    fn maps_channel_items() {
         let channel_json = json!({
-            "id": "channel1",
+            "id": "UCchannel1",
             "name": "Only Channels",
             "handle": null,
             "avatar": [],
@@ -234,8 +928,16 @@ mod tests {
         });
 
         let channel: ChannelItem = serde_json::from_value(channel_json).unwrap();
-        let item = YouTubeItem::Channel(channel);
-
-        assert!(SearchItem::try_from(item).is_err());
+        let mapped = SearchItem::try_from(YouTubeItem::Channel(channel)).unwrap();
+
+        assert_eq!(mapped.id.kind, "youtube#channel");
+        assert_eq!(mapped.id.channel_id.as_deref(), Some("UCchannel1"));
+        assert_eq!(mapped.snippet.title, "Only Channels");
+        assert_eq!(mapped.snippet.description.as_deref(), Some("About"));
+        assert_eq!(
+            uploads_playlist_id("UCchannel1").as_deref(),
+            Some("UUchannel1")
+        );
+        assert_eq!(uploads_playlist_id("handle"), None);
     }
 }
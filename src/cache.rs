@@ -6,8 +6,16 @@ use base64::engine::general_purpose::STANDARD;
 use base64::Engine as _;
 use serde::{Deserialize, Serialize};
 
+/// Version of the on-disk entry format. Entries from older versions are still read (their
+/// data types default missing fields); entries written by a newer ytm are ignored.
+/// Version 1 added duration, views, publish date and the live/short flags to search results.
+pub const FORMAT_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize)]
 pub struct CacheEntry<T> {
+    /// Missing in entries written before versioning, which count as version 0
+    #[serde(default)]
+    pub version: u32,
     pub timestamp_millis: u128,
     pub data: T,
 }
@@ -35,6 +43,9 @@ impl Cache {
         let path = self.key_path(key);
         let raw = fs::read(path).ok()?;
         let entry: CacheEntry<T> = serde_json::from_slice(&raw).ok()?;
+        if entry.version > FORMAT_VERSION {
+            return None;
+        }
 
         let now = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -49,6 +60,7 @@ impl Cache {
 
     pub fn put<T: Serialize>(&self, key: &str, data: &T) -> anyhow::Result<()> {
         let entry = CacheEntry {
+            version: FORMAT_VERSION,
             timestamp_millis: SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)?
                 .as_millis(),
@@ -113,6 +125,31 @@ mod tests {
         assert_eq!(retrieved, None);
     }
 
+    #[test]
+    fn test_cache_format_versions() {
+        let temp_dir = tempdir().unwrap();
+        let cache = Cache::new(temp_dir.path(), Duration::from_secs(60)).unwrap();
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        // Written before entries carried a version
+        let legacy = format!(r#"{{"timestamp_millis":{now},"data":{{"value":"legacy"}}}}"#);
+        fs::write(cache.key_path("legacy_key"), legacy).unwrap();
+        let retrieved: Option<TestData> = cache.get("legacy_key");
+        assert_eq!(retrieved.map(|d| d.value).as_deref(), Some("legacy"));
+
+        // Written by a newer ytm with a format this one does not know
+        let future = format!(
+            r#"{{"version":{},"timestamp_millis":{now},"data":{{"value":"future"}}}}"#,
+            FORMAT_VERSION + 1
+        );
+        fs::write(cache.key_path("future_key"), future).unwrap();
+        let retrieved: Option<TestData> = cache.get("future_key");
+        assert_eq!(retrieved, None);
+    }
+
     #[test]
     fn test_cache_nonexistent_key() {
         let temp_dir = tempdir().unwrap();
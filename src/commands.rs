@@ -1,10 +1,21 @@
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
 
-use crate::api::{SearchItem, YouTubeClient};
+use nix::sys::signal::{self, SigHandler, Signal};
+use serde_json::json;
+
+use crate::api::{self, SearchFilters, SearchItem, YouTubeClient};
 use crate::cache::Cache;
-use crate::mpv::{self, Mpv};
+use crate::config;
+use crate::doctor;
+use crate::history::{self, History, HistoryEntry, Recorder};
+use crate::mpv::{self, Mpv, MpvStatus, Repeat};
+use crate::picker::{self, PickOptions, Picker, PickerEntry};
+use crate::queue::{Queue, QueueEntry};
+use crate::resume::{PlayedPosition, PositionTracker, Positions, ResumePolicy};
+use crate::session;
 
 /// Get the directory for application cache
 fn cache_dir() -> PathBuf {
@@ -13,6 +24,43 @@ fn cache_dir() -> PathBuf {
         .join("ytm")
 }
 
+/// Get the directory for data kept across runs, such as the play history
+fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ytm")
+}
+
+fn history_file() -> PathBuf {
+    data_dir().join("history.jsonl")
+}
+
+/// Get the path of the last playback position per video
+fn positions_file() -> PathBuf {
+    data_dir().join("positions.json")
+}
+
+/// Get the path of the persisted playback queue, one per session
+fn queue_file() -> PathBuf {
+    let session = session::current();
+    if session.is_default() {
+        cache_dir().join("queue.json")
+    } else {
+        cache_dir().join(format!("queue-{}.json", session.name()))
+    }
+}
+
+/// Get the path of the search results shown by this process's picker preview.
+/// One per process, so pickers open in several terminals don't overwrite each other's.
+fn preview_file() -> PathBuf {
+    cache_dir().join(format!("preview-{}.json", std::process::id()))
+}
+
+/// Check whether a user-supplied target is a URL rather than a search query
+fn is_url(target: &str) -> bool {
+    target.starts_with("https://") || target.starts_with("http://")
+}
+
 /// Format seconds as MM:SS string
 fn format_time(seconds: Option<f64>) -> String {
     match seconds {
@@ -25,6 +73,173 @@ fn format_time(seconds: Option<f64>) -> String {
     }
 }
 
+/// Format a length in seconds as M:SS or H:MM:SS
+fn format_duration(seconds: u32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Playback progress through the current track, in whole percent
+fn progress_percent(status: &MpvStatus) -> u32 {
+    match (status.position, status.duration) {
+        (Some(pos), Some(dur)) if dur > 0.0 => (pos / dur * 100.0) as u32,
+        _ => 0,
+    }
+}
+
+/// How `ytm status` prints the player state
+pub enum StatusFormat {
+    Text,
+    Json,
+    /// `--format` template with `{placeholder}`s, see [`render_status`]
+    Template(String),
+}
+
+/// Properties whose changes trigger a new line in `ytm status --follow`
+const FOLLOWED_PROPERTIES: &[&str] = &[
+    "media-title",
+    "pause",
+    "time-pos",
+    "playlist-pos",
+    "shuffle",
+    "loop-file",
+    "loop-playlist",
+];
+
+/// One-line layout used by `ytm status --follow` in text mode
+const FOLLOW_TEMPLATE: &str = "[{state}] {title} — {pos} / {dur}";
+
+/// Exit code of `ytm status` when no player is running
+pub const EXIT_NO_PLAYER: i32 = 3;
+
+/// Fill a `--format` template such as `{title} — {pos}/{dur}`; unknown placeholders are kept as-is
+fn render_status(template: &str, status: &MpvStatus) -> String {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    let number = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 1];
+        let value = match &placeholder[1..placeholder.len() - 1] {
+            "title" => text(&status.title),
+            "channel" => text(&status.channel),
+            "url" => text(&status.url),
+            "id" => text(&status.video_id),
+            "album" => text(&status.album),
+            "playlist" => text(&status.playlist_title),
+            "pos" => format_time(status.position),
+            "dur" => format_time(status.duration),
+            "percent" => progress_percent(status).to_string(),
+            "state" => if status.paused { "paused" } else { "playing" }.to_string(),
+            "volume" => status
+                .volume
+                .map(|v| v.round().to_string())
+                .unwrap_or_default(),
+            "speed" => status
+                .speed
+                .map(|v| format!("{:.2}", v))
+                .unwrap_or_default(),
+            "shuffle" => if status.shuffle { "on" } else { "off" }.to_string(),
+            "repeat" => status.repeat.as_str().to_string(),
+            "playlist_pos" => number(status.playlist_pos),
+            "playlist_count" => number(status.playlist_count),
+            _ => placeholder.to_string(),
+        };
+        out.push_str(&value);
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Format volume, mute and speed as one status line
+fn format_audio(status: &MpvStatus) -> String {
+    let volume = status
+        .volume
+        .map(|v| format!("{}%", v.round()))
+        .unwrap_or_else(|| "--".to_string());
+    let muted = if status.muted { " (muted)" } else { "" };
+    let speed = status.speed.unwrap_or(1.0);
+    format!("volume: {}{}   speed: {:.2}x", volume, muted, speed)
+}
+
+/// Shuffle and repeat state, if either is on
+fn format_modes(status: &MpvStatus) -> Option<String> {
+    match (status.shuffle, status.repeat) {
+        (false, Repeat::Off) => None,
+        (shuffle, repeat) => Some(format!(
+            "shuffle: {}   repeat: {}",
+            if shuffle { "on" } else { "off" },
+            repeat.as_str()
+        )),
+    }
+}
+
+/// Parse a time given as SECONDS, MM:SS or HH:MM:SS
+fn parse_time(value: &str) -> Option<f64> {
+    value.split(':').try_fold(0.0, |total, part| {
+        let part: f64 = part.parse().ok()?;
+        (part >= 0.0).then_some(total * 60.0 + part)
+    })
+}
+
+/// Parse a `ytm seek` argument into an amount and mpv seek flag
+fn parse_seek(arg: &str) -> anyhow::Result<(f64, &'static str)> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid seek target '{}' (use [+/-]SECONDS, MM:SS or N%)",
+            arg
+        )
+    };
+
+    let (sign, value) = match arg.chars().next() {
+        Some('+') => (Some(1.0), &arg[1..]),
+        Some('-') => (Some(-1.0), &arg[1..]),
+        _ => (None, arg),
+    };
+
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent: f64 = percent.parse().map_err(|_| invalid())?;
+        return Ok(match sign {
+            Some(sign) => (sign * percent, "relative-percent"),
+            None => (percent, "absolute-percent"),
+        });
+    }
+
+    let seconds = parse_time(value).ok_or_else(invalid)?;
+    Ok(match sign {
+        Some(sign) => (sign * seconds, "relative"),
+        None => (seconds, "absolute"),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VolumeChange {
+    Set(f64),
+    Adjust(f64),
+}
+
+/// Parse a `ytm volume` argument: `N` sets, `+N`/`-N` adjusts
+fn parse_volume(arg: &str) -> anyhow::Result<VolumeChange> {
+    let amount: f64 = arg
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid volume '{}' (use N, +N or -N)", arg))?;
+    if arg.starts_with(['+', '-']) {
+        Ok(VolumeChange::Adjust(amount))
+    } else {
+        Ok(VolumeChange::Set(amount))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct SearchChoice {
     label: String,
@@ -37,10 +252,42 @@ impl SearchChoice {
         let channel = item.snippet.channel_title.trim();
 
         if let Some(id) = item.id.video_id.as_ref() {
-            let label = format!("{} | {}", title, channel);
+            let mut label = format!("{} | {}", title, channel);
+            if item.snippet.is_live {
+                label.push_str(" [LIVE]");
+            } else if let Some(duration) = item.snippet.duration {
+                label.push_str(&format!(" [{}]", format_duration(duration)));
+            }
+            if item.snippet.is_short {
+                label.push_str(" [SHORT]");
+            }
+            let target = if item.id.kind == "ytmusic#track" {
+                SearchTarget::Song(id.clone())
+            } else {
+                SearchTarget::Video(id.clone())
+            };
+            return Some(Self { label, target });
+        }
+
+        if let Some(id) = item.id.album_id.as_ref() {
+            let kind = item.snippet.description.as_deref().unwrap_or("Album");
             return Some(Self {
-                label,
-                target: SearchTarget::Video(id.clone()),
+                label: format!("{} | {} [{}]", title, channel, kind),
+                target: SearchTarget::Album(id.clone()),
+            });
+        }
+
+        if let Some(id) = item.id.artist_id.as_ref() {
+            return Some(Self {
+                label: format!("{} [artist]", title),
+                target: SearchTarget::Artist(id.clone()),
+            });
+        }
+
+        if let Some(id) = item.id.channel_id.as_ref() {
+            return Some(Self {
+                label: format!("{} [channel]", title),
+                target: SearchTarget::Channel(id.clone()),
             });
         }
 
@@ -49,30 +296,205 @@ impl SearchChoice {
             target: SearchTarget::Playlist(id.clone()),
         })
     }
+
+    fn into_entry(self) -> QueueEntry {
+        QueueEntry {
+            url: self.target.url(),
+            title: self.label,
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum SearchTarget {
     Video(String),
     Playlist(String),
+    /// Channel, browsed in a second picker
+    Channel(String),
+    /// YouTube Music song
+    Song(String),
+    /// YouTube Music album, played as its track list
+    Album(String),
+    /// YouTube Music artist, played as their songs
+    Artist(String),
 }
 
 impl SearchTarget {
+    /// Stable identifier used to find the result again, e.g. from the picker preview
+    fn key(&self) -> String {
+        format!("{}:{}", self.kind(), self.id())
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            SearchTarget::Video(_) => "video",
+            SearchTarget::Playlist(_) => "playlist",
+            SearchTarget::Channel(_) => "channel",
+            SearchTarget::Song(_) => "song",
+            SearchTarget::Album(_) => "album",
+            SearchTarget::Artist(_) => "artist",
+        }
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            SearchTarget::Video(id)
+            | SearchTarget::Playlist(id)
+            | SearchTarget::Channel(id)
+            | SearchTarget::Song(id)
+            | SearchTarget::Album(id)
+            | SearchTarget::Artist(id) => id,
+        }
+    }
+
     fn url(&self) -> String {
         match self {
             SearchTarget::Video(id) => format!("https://www.youtube.com/watch?v={id}"),
             SearchTarget::Playlist(id) => format!("https://www.youtube.com/playlist?list={id}"),
+            SearchTarget::Channel(id) => format!("https://www.youtube.com/channel/{id}"),
+            SearchTarget::Song(id) => format!("https://music.youtube.com/watch?v={id}"),
+            SearchTarget::Album(id) => format!("https://music.youtube.com/browse/{id}"),
+            SearchTarget::Artist(id) => format!("https://music.youtube.com/channel/{id}"),
+        }
+    }
+}
+
+/// Replace picked channels with what the user picks from their uploads and playlists
+async fn browse_channels(
+    picker: Picker,
+    choices: Vec<SearchChoice>,
+) -> anyhow::Result<Vec<SearchChoice>> {
+    let mut browsed = Vec::new();
+    for choice in choices {
+        let SearchTarget::Channel(id) = &choice.target else {
+            browsed.push(choice);
+            continue;
+        };
+        let items = youtube_client()?.channel_contents(id).await?;
+        let channel_choices: Vec<SearchChoice> =
+            items.iter().filter_map(SearchChoice::from_item).collect();
+        if channel_choices.is_empty() {
+            eprintln!("Nothing to play on {}", choice.label);
+            continue;
+        }
+        browsed.extend(pick_choices(picker, &items, channel_choices)?);
+    }
+    Ok(browsed)
+}
+
+/// Turn picked choices into queue entries, expanding playlists, albums and artists into
+/// their tracks. With `track_picker`, the user picks which tracks to play.
+async fn resolve_entries(
+    choices: Vec<SearchChoice>,
+    track_picker: Option<Picker>,
+) -> anyhow::Result<Vec<QueueEntry>> {
+    let mut entries = Vec::new();
+    for choice in choices {
+        let list = match &choice.target {
+            SearchTarget::Playlist(id) => match youtube_client()?.playlist_tracks(id).await {
+                Ok(list) => list,
+                Err(err) => {
+                    // Mixes and other generated playlists can't be listed; leave them to yt-dlp
+                    eprintln!("Could not list {}: {:#}", choice.label, err);
+                    entries.push(choice.into_entry());
+                    continue;
+                }
+            },
+            SearchTarget::Album(id) => youtube_client()?.music_album_tracks(id).await?,
+            SearchTarget::Artist(id) => youtube_client()?.music_artist_tracks(id).await?,
+            _ => {
+                entries.push(choice.into_entry());
+                continue;
+            }
+        };
+
+        let (track_choices, tracks): (Vec<SearchChoice>, Vec<QueueEntry>) = list
+            .items
+            .iter()
+            .filter_map(|item| {
+                let track = SearchChoice::from_item(item)?;
+                let entry = QueueEntry {
+                    url: track.target.url(),
+                    title: track.label.clone(),
+                    media_title: Some(item.snippet.title.clone()),
+                    playlist: Some(list.title.clone()),
+                };
+                Some((track, entry))
+            })
+            .unzip();
+        if tracks.is_empty() {
+            eprintln!("No playable tracks in {}", choice.label);
+            continue;
+        }
+
+        match track_picker {
+            Some(picker) => {
+                let picked = pick_indices(picker, &list.items, &track_choices)?;
+                entries.extend(select_tracks(tracks, &picked));
+            }
+            None => entries.extend(tracks),
         }
     }
+    Ok(entries)
 }
 
-/// Simple status display
-fn show_detailed_status() -> anyhow::Result<()> {
-    std::thread::sleep(std::time::Duration::from_secs(1));
+/// Tracks to play after picking from a list: a single pick starts the list at that track,
+/// several picks play just those, in the order they were picked
+fn select_tracks(mut tracks: Vec<QueueEntry>, picked: &[usize]) -> Vec<QueueEntry> {
+    match picked {
+        [] => Vec::new(),
+        [start] => tracks.split_off(*start),
+        _ => picked.iter().map(|&index| tracks[index].clone()).collect(),
+    }
+}
 
-    if let Ok(mut mpv_client) = Mpv::connect() {
-        if let Ok(status) = mpv_client.get_status() {
-            if let Some(title) = status.title {
+/// Player status, with the playlist name taken from the queue for expanded playlists
+fn player_status(client: &mut Mpv) -> anyhow::Result<MpvStatus> {
+    let mut status = client.get_status()?;
+    let playlist = status.playlist_pos.and_then(|pos| {
+        let index = usize::try_from(pos).ok()?.checked_sub(1)?;
+        let queue = Queue::load(queue_file()).ok()?;
+        // mpv may have expanded a URL into several entries, shifting the positions
+        let entry = queue.entries().get(index)?;
+        (status.url.as_ref() == Some(&entry.url))
+            .then(|| entry.playlist.clone())
+            .flatten()
+    });
+    if playlist.is_some() {
+        status.playlist_title = playlist;
+    }
+    Ok(status)
+}
+
+/// What the player is expected to be doing when its status is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusWait {
+    /// Already playing; show its status right away
+    No,
+    /// Switching tracks, which it may also have quit at the end of the playlist
+    Switch,
+    /// Just spawned, so its socket may not be up yet
+    Start,
+}
+
+/// Simple status display. A switching or freshly started player gets time to load
+/// the file before its status is read.
+fn show_detailed_status(wait: StatusWait) -> anyhow::Result<()> {
+    let client = match wait {
+        StatusWait::Start => Mpv::connect_timeout(Duration::from_secs(5)),
+        StatusWait::No | StatusWait::Switch => Mpv::connect(),
+    };
+    let client = client.and_then(|mut client| {
+        if wait != StatusWait::No {
+            client.wait_until_playing(Duration::from_secs(10))?;
+        }
+        Ok(client)
+    });
+
+    if let Ok(mut mpv_client) = client {
+        if let Ok(status) = player_status(&mut mpv_client) {
+            if let Some(title) = &status.title {
                 println!("{}", title);
 
                 // Show album or playlist title
@@ -84,16 +506,7 @@ fn show_detailed_status() -> anyhow::Result<()> {
 
                 let pos_str = format_time(status.position);
                 let dur_str = format_time(status.duration);
-                let percentage = if let (Some(pos), Some(dur)) = (status.position, status.duration)
-                {
-                    if dur > 0.0 {
-                        (pos / dur * 100.0) as u32
-                    } else {
-                        0
-                    }
-                } else {
-                    0
-                };
+                let percentage = progress_percent(&status);
 
                 // Format playlist info
                 let playlist_info = if let (Some(pos), Some(count)) =
@@ -112,207 +525,1358 @@ fn show_detailed_status() -> anyhow::Result<()> {
                     "{}   {} / {} ({}%)",
                     playlist_info, pos_str, dur_str, percentage
                 );
-                println!("volume: 100%");
+                println!("{}", format_audio(&status));
+                if let Some(modes) = format_modes(&status) {
+                    println!("{}", modes);
+                }
                 return Ok(());
             }
         }
     }
 
-    println!("Player not responding");
+    match wait {
+        StatusWait::Start => println!(
+            "Player did not start playing; run without -b to see mpv's output, or {}",
+            doctor::HINT
+        ),
+        // mpv quits after the last track of the playlist
+        StatusWait::Switch if !mpv::is_running() => println!("Player stopped"),
+        _ => println!("Player not responding"),
+    }
     Ok(())
 }
 
-/// Search YouTube, pick first result, and play
-pub async fn search_and_play(query: &str, no_video: bool, background: bool) -> anyhow::Result<()> {
+/// What `ytm search` does with the results
+pub enum SearchMode {
+    /// Pick a result interactively
+    Interactive(Picker),
+    /// Print results as tab-separated values
+    List,
+    /// Print results as a JSON array
+    Json,
+    /// Play the Nth result (1-based) without a picker
+    Pick(usize),
+}
+
+/// Flat, script-friendly view of a search result for `--list`/`--json`
+#[derive(Debug, serde::Serialize)]
+struct SearchRow {
+    id: String,
+    kind: &'static str,
+    title: String,
+    channel: String,
+    description: Option<String>,
+    url: String,
+    duration: Option<u32>,
+    view_count: Option<u64>,
+    published_at: Option<String>,
+    thumbnail_url: Option<String>,
+    is_live: bool,
+    is_short: bool,
+}
+
+impl SearchRow {
+    fn from_item(item: &SearchItem) -> Option<Self> {
+        let choice = SearchChoice::from_item(item)?;
+        Some(Self {
+            id: choice.target.id().to_string(),
+            kind: choice.target.kind(),
+            title: item.snippet.title.clone(),
+            channel: item.snippet.channel_title.clone(),
+            description: item.snippet.description.clone(),
+            url: choice.target.url(),
+            duration: item.snippet.duration,
+            view_count: item.snippet.view_count,
+            published_at: item.snippet.published_at.clone(),
+            thumbnail_url: item.snippet.thumbnail_url.clone(),
+            is_live: item.snippet.is_live,
+            is_short: item.snippet.is_short,
+        })
+    }
+
+    /// One TSV line: id, kind, title, channel, description, url
+    fn to_tsv(&self) -> String {
+        let clean = |field: &str| field.replace(['\t', '\n', '\r'], " ");
+        [
+            self.id.as_str(),
+            self.kind,
+            &self.title,
+            &self.channel,
+            self.description.as_deref().unwrap_or(""),
+            &self.url,
+        ]
+        .map(clean)
+        .join("\t")
+    }
+}
+
+/// YouTube client backed by the search cache
+fn youtube_client() -> anyhow::Result<YouTubeClient> {
     let cache_root = cache_dir();
-    let cache = Cache::new(&cache_root, std::time::Duration::from_secs(3600))?;
-    let client = YouTubeClient::new(&cache_root, cache)?;
+    let cache = Cache::new(
+        &cache_root,
+        Duration::from_secs(config::current().cache_ttl),
+    )?;
+    YouTubeClient::new(&cache_root, cache)
+}
 
-    // Fetch 50 results
-    let results = client.search(query, Some(50)).await?;
+/// Search YouTube for playable videos and playlists
+async fn search_items(query: &str, filters: &SearchFilters) -> anyhow::Result<Vec<SearchItem>> {
+    let results = youtube_client()?
+        .search(query, Some(config::current().results), filters)
+        .await?;
     if results.is_empty() {
         return Err(anyhow::anyhow!("No results for '{}'", query));
     }
+    Ok(results)
+}
 
-    let choices: Vec<SearchChoice> =
-        results.iter().filter_map(SearchChoice::from_item).collect();
+/// Turn search results into picker choices
+fn search_choices(query: &str, results: &[SearchItem]) -> anyhow::Result<Vec<SearchChoice>> {
+    let choices: Vec<SearchChoice> = results.iter().filter_map(SearchChoice::from_item).collect();
 
     if choices.is_empty() {
-        return Err(anyhow::anyhow!(
-            "No playable videos or playlists found for '{}'",
-            query
-        ));
+        return Err(anyhow::anyhow!("No playable results found for '{}'", query));
     }
+    Ok(choices)
+}
 
-    let fzf = Command::new("fzf")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()?;
-    {
-        let mut stdin = fzf.stdin.as_ref().unwrap();
-        for choice in &choices {
-            writeln!(stdin, "{}", choice.label)?;
-        }
+/// Let the user pick one or more choices, in the order they were selected
+fn pick_choices(
+    picker: Picker,
+    results: &[SearchItem],
+    choices: Vec<SearchChoice>,
+) -> anyhow::Result<Vec<SearchChoice>> {
+    Ok(pick_indices(picker, results, &choices)?
+        .into_iter()
+        .map(|index| choices[index].clone())
+        .collect())
+}
+
+/// Let the user pick among `choices`, returning their indices in selection order
+fn pick_indices(
+    picker: Picker,
+    results: &[SearchItem],
+    choices: &[SearchChoice],
+) -> anyhow::Result<Vec<usize>> {
+    // The preview command runs in a separate ytm process, which reads the results from here
+    let items: HashMap<String, &SearchItem> = results
+        .iter()
+        .filter_map(|item| Some((SearchChoice::from_item(item)?.target.key(), item)))
+        .collect();
+    let preview_file = preview_file();
+    std::fs::write(&preview_file, serde_json::to_vec(&items)?)?;
+
+    let preview = format!(
+        "{} __preview {} {{}}",
+        shell_quote(&std::env::current_exe()?.to_string_lossy()),
+        shell_quote(&preview_file.to_string_lossy())
+    );
+    let entries: Vec<PickerEntry> = choices
+        .iter()
+        .map(|choice| PickerEntry {
+            key: choice.target.key(),
+            label: choice.label.clone(),
+        })
+        .collect();
+    let options = PickOptions {
+        multi: true,
+        preview: Some(&preview),
+    };
+
+    let picked = picker.pick(&entries, &options);
+    let _ = std::fs::remove_file(&preview_file);
+    picked
+}
+
+/// Search YouTube and let the user pick results
+async fn search_and_pick(
+    query: &str,
+    filters: &SearchFilters,
+    picker: Picker,
+) -> anyhow::Result<Vec<SearchChoice>> {
+    let results = search_items(query, filters).await?;
+    let choices = search_choices(query, &results)?;
+    let picked = pick_choices(picker, &results, choices)?;
+    browse_channels(picker, picked).await
+}
+
+/// Print details of a search result, saved by the picker in `file`, for its preview pane
+pub fn preview(file: &Path, key: &str) -> anyhow::Result<()> {
+    let raw = std::fs::read(file)?;
+    let items: HashMap<String, SearchItem> = serde_json::from_slice(&raw)?;
+    let item = items
+        .get(key)
+        .ok_or_else(|| anyhow::anyhow!("No preview for '{}'", key))?;
+
+    let snippet = &item.snippet;
+    println!("{}", snippet.title);
+    println!("{}", snippet.channel_title);
+    println!();
+    if snippet.is_live {
+        println!("Duration:  LIVE");
+    } else if let Some(duration) = snippet.duration {
+        println!("Duration:  {}", format_duration(duration));
+    }
+    if let Some(views) = snippet.view_count {
+        println!("Views:     {}", views);
+    }
+    if let Some(published) = &snippet.published_at {
+        println!("Uploaded:  {}", published);
+    }
+    if snippet.is_short {
+        println!("Short");
     }
-    let output = fzf.wait_with_output()?;
-    if !output.status.success() {
+    if let Some(target) = SearchChoice::from_item(item).map(|choice| choice.target) {
+        println!("{}", target.url());
+    }
+    if let Some(description) = &snippet.description {
+        println!();
+        println!("{}", description);
+    }
+    Ok(())
+}
+
+/// Quote a string for use in a POSIX shell command
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Search YouTube, then pick and play a result or print the results, depending on `mode`.
+/// With `pick_tracks`, an interactive search also picks tracks from a picked playlist.
+pub async fn search_and_play(
+    query: &str,
+    filters: &SearchFilters,
+    mode: &SearchMode,
+    pick_tracks: bool,
+    options: &PlayOptions,
+) -> anyhow::Result<()> {
+    let results = search_items(query, filters).await?;
+
+    let picked = match mode {
+        SearchMode::List => {
+            for row in results.iter().filter_map(SearchRow::from_item) {
+                println!("{}", row.to_tsv());
+            }
+            return Ok(());
+        }
+        SearchMode::Json => {
+            let rows: Vec<SearchRow> = results.iter().filter_map(SearchRow::from_item).collect();
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+            return Ok(());
+        }
+        SearchMode::Interactive(picker) => {
+            pick_choices(*picker, &results, search_choices(query, &results)?)?
+        }
+        SearchMode::Pick(n) => {
+            let mut choices = search_choices(query, &results)?;
+            if *n == 0 || *n > choices.len() {
+                return Err(anyhow::anyhow!(
+                    "No result #{} for '{}' ({} results)",
+                    n,
+                    query,
+                    choices.len()
+                ));
+            }
+            vec![choices.swap_remove(n - 1)]
+        }
+    };
+
+    if picked.is_empty() {
         return Ok(());
     }
-    let selected = String::from_utf8_lossy(&output.stdout);
-    if selected.trim().is_empty() {
+    // Without an interactive picker, a picked channel plays all its uploads
+    let channel_picker = match mode {
+        SearchMode::Interactive(picker) => *picker,
+        _ => Picker::None,
+    };
+    let picked = browse_channels(channel_picker, picked).await?;
+    if picked.is_empty() {
         return Ok(());
     }
-    let selected_line = selected.trim();
-    let choice = choices
-        .into_iter()
-        .find(|candidate| candidate.label == selected_line)
-        .ok_or_else(|| anyhow::anyhow!("Selection not found"))?;
+    let track_picker = match mode {
+        SearchMode::Interactive(picker) if pick_tracks => Some(*picker),
+        _ => None,
+    };
+    let entries = resolve_entries(picked, track_picker).await?;
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!("Nothing to play for '{}'", query));
+    }
+    play_entries(entries, options)
+}
+
+/// Play a single URL
+pub fn play(url: &str, options: &PlayOptions) -> anyhow::Result<()> {
+    let entry = QueueEntry {
+        url: url.to_string(),
+        title: url.to_string(),
+        ..Default::default()
+    };
+    play_entries(vec![entry], options)
+}
+
+/// What `--loop` repeats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LoopMode {
+    Track,
+    Playlist,
+}
 
-    play(&choice.target.url(), no_video, background)
+/// How a new player is started
+#[derive(Debug, Clone, Default)]
+pub struct PlayOptions {
+    /// Audio only (the default; -v turns video on)
+    pub no_video: bool,
+    /// Detach mpv and control it over IPC
+    pub background: bool,
+    /// Play the entries in random order
+    pub shuffle: bool,
+    pub looping: Option<LoopMode>,
+    /// Whether to continue videos where they were left off
+    pub resume: ResumePolicy,
+    /// Extra mpv options from the command line, after the configured ones
+    pub mpv_args: Vec<String>,
 }
 
-/// Start mpv either foreground or background with IPC enabled
-pub fn play(url: &str, no_video: bool, background: bool) -> anyhow::Result<()> {
+/// mpv arguments for one queue entry, giving it its own title where known and starting it
+/// `start` seconds in
+fn entry_args(entry: &QueueEntry, start: Option<f64>) -> Vec<String> {
+    let mut options = Vec::new();
+    if let Some(title) = &entry.media_title {
+        options.push(format!("--force-media-title={}", title));
+    }
+    if let Some(start) = start {
+        options.push(format!("--start={:.0}", start));
+    }
+    if options.is_empty() {
+        return vec![entry.url.clone()];
+    }
+    let mut args = vec!["--{".to_string()];
+    args.extend(options);
+    args.push(entry.url.clone());
+    args.push("--}".to_string());
+    args
+}
+
+/// Start mpv either foreground or background with IPC enabled.
+/// The given entries become the new queue.
+fn play_entries(entries: Vec<QueueEntry>, options: &PlayOptions) -> anyhow::Result<()> {
+    // mpv only reports a missing yt-dlp in its own output, which -b discards. Its ytdl_hook
+    // takes every http(s) URL; local files and other streams play without it.
+    if picker::find_in_path("yt-dlp").is_none() {
+        if entries.iter().any(|entry| is_url(&entry.url)) {
+            return Err(anyhow::anyhow!(
+                "yt-dlp not found on PATH; mpv needs it to play YouTube ({})",
+                doctor::HINT
+            ));
+        }
+        eprintln!("yt-dlp not found on PATH; playing anyway, but mpv needs it for YouTube URLs");
+    }
+    let mut queue = Queue::load(queue_file())?;
+    queue.replace(entries);
+    queue.save()?;
+
+    // A foreground player gets a socket of its own, only used to record its history
+    let socket = if options.background {
+        mpv::mpv_socket().to_path_buf()
+    } else {
+        mpv::foreground_socket()
+    };
+    let ipc_server = format!("--input-ipc-server={}", socket.display());
+    let settings = config::current();
+    let ytdl_format = format!("--ytdl-format={}", settings.format(options.no_video));
+    // Appended as a single option, as extractor-args may contain commas
+    let extractor_args = settings
+        .extractor_args()
+        .map(|args| format!("--ytdl-raw-options-append=extractor-args={}", args));
     let mut args = Vec::new();
-    if no_video {
+    if options.no_video {
         args.push("--no-video");
-        args.push("--ytdl-format=bestaudio/best");
-    } else {
-        // Let yt-dlp pick the best adaptive combo, falling back to a single progressive stream
-        args.push("--ytdl-format=bestvideo+bestaudio/best");
     }
-    // Ask yt-dlp to use the Android client profile to prefer AVC/MP4 streams
-    args.push("--ytdl-raw-options=extractor-args=youtube:player_client=android");
+    args.push(&ytdl_format);
+    if let Some(extractor_args) = &extractor_args {
+        args.push(extractor_args);
+    }
     // Force mpv's ytdl_hook to use yt-dlp when available in PATH for consistent format handling
     args.push("--script-opts=ytdl_hook-ytdl_path=yt-dlp");
-    if background {
-        args.push("--input-ipc-server=/tmp/ytm-mpv.sock");
+    if options.shuffle {
+        args.push("--shuffle");
+    }
+    match options.looping {
+        Some(LoopMode::Track) => args.push("--loop-file=inf"),
+        Some(LoopMode::Playlist) => args.push("--loop-playlist=inf"),
+        None => {}
     }
-    args.push(url);
+    // Applied after ytm's own options, so the profile and extra options can override them
+    let profile = format!("--profile={}", mpv::PROFILE);
+    if mpv::has_profile(mpv::PROFILE) {
+        args.push(&profile);
+    }
+    args.extend(settings.mpv_args.iter().map(String::as_str));
+    args.extend(options.mpv_args.iter().map(String::as_str));
+    session::current().ensure_socket_dir()?;
+    args.push(&ipc_server);
+    let positions = Positions::load(positions_file());
+    let file_args: Vec<String> = queue
+        .entries()
+        .iter()
+        .flat_map(|entry| {
+            let start = api::video_id_from_url(&entry.url)
+                .and_then(|id| positions.get(&id).copied())
+                .and_then(|saved| saved.start(options.resume));
+            entry_args(entry, start)
+        })
+        .collect();
+    args.extend(file_args.iter().map(String::as_str));
 
-    if background {
+    if options.background {
         // Start mpv in background with output suppressed from the start
-        let _child = Command::new("mpv")
+        let mut child = Command::new("mpv")
             .args(&args)
             .stdout(std::process::Stdio::null()) // Suppress stdout immediately
             .stderr(std::process::Stdio::null()) // Suppress stderr immediately
             .stdin(std::process::Stdio::null()) // Also suppress stdin
-            .spawn()?;
+            .spawn()
+            .map_err(spawn_error)?;
+        // With its output gone, a player that quits before opening its socket needs explaining
+        if Mpv::connect_timeout(Duration::from_secs(5)).is_err() {
+            if let Some(status) = child.try_wait()? {
+                return Err(anyhow::anyhow!(
+                    "mpv quit ({}) before playing; run without -b to see its output, or {}",
+                    status,
+                    doctor::HINT
+                ));
+            }
+        }
+        // Lets `ytm stop` kill the player even when its socket stops answering. The player
+        // is already up, so without it only that fallback is lost.
+        if let Err(err) = mpv::write_pid_file(child.id()) {
+            eprintln!("Could not record the player's pid: {:#}", err);
+        }
+        spawn_watcher()?;
+
+        if options.shuffle {
+            // mpv shuffled the playlist on start; keep the queue in the same order
+            if let Ok(mut client) = Mpv::connect_timeout(Duration::from_secs(5)) {
+                sync_queue_order(&mut client)?;
+            }
+        }
 
         // Show status with retry logic
-        show_detailed_status()?;
+        show_detailed_status(StatusWait::Start)?;
         println!("\nPlayer started in background. Use 'ytm stop/pause/next/prev' to control.");
 
         Ok(())
     } else {
-        // Run in foreground (blocking)
-        Command::new("mpv").args(&args).status()?;
+        // Run in foreground (blocking), recording the history from a thread
+        let mut child = Command::new("mpv")
+            .args(&args)
+            .spawn()
+            .map_err(spawn_error)?;
+        let watched_socket = socket.clone();
+        let watcher = std::thread::spawn(move || {
+            Mpv::connect_at_timeout(&watched_socket, Duration::from_secs(5))
+                .and_then(|mut client| record_playback(&mut client))
+        });
+        // Ctrl+C reaches mpv too; outlive it so the watcher can record the last track and
+        // the socket gets removed
+        let previous = ignore_sigint();
+        let waited = child.wait();
+        restore_sigint(previous);
+        waited?;
+        let _ = watcher.join();
+        let _ = std::fs::remove_file(&socket);
         Ok(())
     }
 }
 
-pub fn pause() -> anyhow::Result<()> {
-    use serde_json::json;
-    mpv::send_mpv_command(json!({"command": ["cycle", "pause"]}))
+/// Ignore SIGINT in this process, returning the handler to put back afterwards
+fn ignore_sigint() -> Option<SigHandler> {
+    // SAFETY: SIG_IGN runs no code in the signal context
+    unsafe { signal::signal(Signal::SIGINT, SigHandler::SigIgn) }.ok()
 }
 
-pub fn resume() -> anyhow::Result<()> {
-    use serde_json::json;
-    mpv::send_mpv_command(json!({"command": ["cycle", "pause"]}))
+fn restore_sigint(previous: Option<SigHandler>) {
+    if let Some(handler) = previous {
+        // SAFETY: puts back the handler that was installed before `ignore_sigint`
+        let _ = unsafe { signal::signal(Signal::SIGINT, handler) };
+    }
 }
 
-pub fn next() -> anyhow::Result<()> {
-    use serde_json::json;
-
-    // Get current track position before change
-    let current_pos = if let Ok(mut client) = Mpv::connect() {
-        client
-            .get_property("playlist-pos-1")
-            .ok()
-            .flatten()
-            .and_then(|v| v.as_i64())
+/// Error for mpv failing to start, explaining a missing mpv
+fn spawn_error(err: std::io::Error) -> anyhow::Error {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        anyhow::anyhow!("mpv not found on PATH; install it, or {}", doctor::HINT)
     } else {
-        None
+        anyhow::Error::new(err).context("failed to start mpv")
+    }
+}
+
+/// Start `ytm __watch` for the session's background player
+fn spawn_watcher() -> anyhow::Result<()> {
+    let session = session::current();
+    Command::new(std::env::current_exe()?)
+        .args(["--session", session.name()])
+        .arg("--socket")
+        .arg(session.socket())
+        .arg("__watch")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Follow the session's player until it quits, recording what it plays
+pub fn watch() -> anyhow::Result<()> {
+    let mut client = Mpv::connect_timeout(Duration::from_secs(5))?;
+    record_playback(&mut client)
+}
+
+/// History entry for the track in `status`, starting now
+fn history_entry(status: &MpvStatus) -> HistoryEntry {
+    let url = status.url.clone().unwrap_or_default();
+    HistoryEntry {
+        video_id: status.video_id.clone(),
+        title: status.title.clone().unwrap_or_else(|| url.clone()),
+        url,
+        channel: status.channel.clone(),
+        played_at: history::now(),
+        listened: 0.0,
+    }
+}
+
+/// Player status, or `None` once the player has gone away
+fn status_unless_gone(client: &mut Mpv) -> anyhow::Result<Option<MpvStatus>> {
+    match client.get_status() {
+        Ok(status) => Ok(Some(status)),
+        Err(err) if mpv::is_disconnected(&err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Remember how far a video was played, so the next play can continue from there
+fn save_position(played: Option<PlayedPosition>) -> anyhow::Result<()> {
+    let Some(played) = played else {
+        return Ok(());
     };
+    // Loaded afresh every time, as other players may have saved positions meanwhile
+    let mut positions = Positions::load(positions_file());
+    positions.record(&played, history::now());
+    positions.save()
+}
+
+/// Append every track the player starts to the history, with how long it was listened to,
+/// and keep the position reached in each
+fn record_playback(client: &mut Mpv) -> anyhow::Result<()> {
+    let history = History::new(history_file());
+    let mut recorder = Recorder::default();
+    let mut tracker = PositionTracker::default();
+    let mut paused = false;
+    // All report their current value right away, so a track that is already playing counts
+    client.observe_property(1, "pause")?;
+    client.observe_property(2, "playlist-pos")?;
+    client.observe_property(3, "time-pos")?;
 
-    mpv::send_mpv_command(json!({"command": ["playlist-next", "force"]}))?;
-
-    // Wait for actual track position change (not timing)
-    for _ in 0..10 {
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        if let Ok(mut client) = Mpv::connect() {
-            if let Ok(Some(new_pos)) = client
-                .get_property("playlist-pos-1")
-                .map(|v| v.and_then(|val| val.as_i64()))
-            {
-                if Some(new_pos) != current_pos {
-                    // Position changed, show status
-                    show_detailed_status()?;
-                    return Ok(());
+    loop {
+        let event = match client.next_event() {
+            Ok(event) => event,
+            Err(err) if mpv::is_disconnected(&err) => break,
+            Err(err) => return Err(err),
+        };
+        let name = event.get("name").and_then(|n| n.as_str());
+        match event.get("event").and_then(|e| e.as_str()) {
+            Some("property-change") if name == Some("pause") => {
+                paused = event.get("data").and_then(|d| d.as_bool()) == Some(true);
+                recorder.set_paused(paused);
+            }
+            Some("property-change") if name == Some("playlist-pos") => {
+                let playing = event
+                    .get("data")
+                    .and_then(|d| d.as_i64())
+                    .is_some_and(|pos| pos >= 0);
+                let finished = if playing {
+                    let Some(status) = status_unless_gone(client)? else {
+                        break;
+                    };
+                    save_position(tracker.start(status.video_id.clone()))?;
+                    // Already known when the watcher attaches to a track that is playing
+                    tracker.set_duration(status.duration);
+                    recorder.start(history_entry(&status), paused)
+                } else {
+                    save_position(tracker.finish())?;
+                    recorder.finish()
+                };
+                if let Some(finished) = finished {
+                    history.append(&finished)?;
+                }
+            }
+            Some("property-change") if name == Some("time-pos") => {
+                if let Some(position) = event.get("data").and_then(|d| d.as_f64()) {
+                    save_position(tracker.update(position))?;
                 }
             }
+            // The title and channel are only known once yt-dlp has resolved the track
+            Some("file-loaded") => {
+                let Some(status) = status_unless_gone(client)? else {
+                    break;
+                };
+                tracker.set_duration(status.duration);
+                if let Some(entry) = recorder.current_mut() {
+                    let loaded = history_entry(&status);
+                    entry.title = loaded.title;
+                    entry.channel = loaded.channel;
+                    entry.video_id = loaded.video_id;
+                }
+            }
+            Some("shutdown") => break,
+            _ => {}
         }
     }
 
-    // Fallback if position detection fails
-    show_detailed_status()?;
+    save_position(tracker.finish())?;
+    if let Some(finished) = recorder.finish() {
+        history.append(&finished)?;
+    }
     Ok(())
 }
 
-pub fn prev() -> anyhow::Result<()> {
-    use serde_json::json;
+pub fn pause() -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    client.set_pause(true)?;
+    print_pause_state(&mut client)
+}
 
-    // Get current track position before change
-    let current_pos = if let Ok(mut client) = Mpv::connect() {
-        client
-            .get_property("playlist-pos-1")
-            .ok()
-            .flatten()
-            .and_then(|v| v.as_i64())
+pub fn resume() -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    client.set_pause(false)?;
+    print_pause_state(&mut client)
+}
+
+pub fn toggle() -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    client.toggle_pause()?;
+    print_pause_state(&mut client)
+}
+
+/// Print the pause state as mpv reports it after a change
+fn print_pause_state(client: &mut Mpv) -> anyhow::Result<()> {
+    if client.is_paused()? {
+        println!("Paused");
     } else {
-        None
+        println!("Playing");
+    }
+    Ok(())
+}
+
+pub fn volume(change: Option<&str>) -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    match change.map(parse_volume).transpose()? {
+        Some(VolumeChange::Set(volume)) => client.set_property("volume", json!(volume))?,
+        Some(VolumeChange::Adjust(delta)) => client.add_volume(delta)?,
+        None => {}
+    }
+    println!("{}", format_audio(&client.get_status()?));
+    Ok(())
+}
+
+pub fn mute() -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    client.toggle_mute()?;
+    println!("{}", format_audio(&client.get_status()?));
+    Ok(())
+}
+
+pub fn seek(target: &str) -> anyhow::Result<()> {
+    let (amount, flag) = parse_seek(target)?;
+    let mut client = Mpv::connect()?;
+    client.seek(amount, flag)?;
+
+    let status = client.get_status()?;
+    println!(
+        "{} / {}",
+        format_time(status.position),
+        format_time(status.duration)
+    );
+    Ok(())
+}
+
+pub fn speed(speed: Option<f64>) -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    if let Some(speed) = speed {
+        client.set_property("speed", json!(speed))?;
+    }
+    println!("{}", format_audio(&client.get_status()?));
+    Ok(())
+}
+
+/// Turn shuffle on or off, toggling it without `on`
+pub fn shuffle(on: Option<bool>) -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    let on = match on {
+        Some(on) => on,
+        None => !client.get_status()?.shuffle,
     };
+    client.set_shuffle(on)?;
+    sync_queue_order(&mut client)?;
+    println!("shuffle: {}", if on { "on" } else { "off" });
+    Ok(())
+}
 
-    mpv::send_mpv_command(json!({"command": ["playlist-prev", "force"]}))?;
-
-    // Wait for actual track position change (not timing)
-    for _ in 0..10 {
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        if let Ok(mut client) = Mpv::connect() {
-            if let Ok(Some(new_pos)) = client
-                .get_property("playlist-pos-1")
-                .map(|v| v.and_then(|val| val.as_i64()))
-            {
-                if Some(new_pos) != current_pos {
-                    // Position changed, show status
-                    show_detailed_status()?;
-                    return Ok(());
-                }
-            }
-        }
+/// Set the repeat mode, or print it without `mode`
+pub fn repeat(mode: Option<Repeat>) -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    if let Some(mode) = mode {
+        client.set_repeat(mode)?;
     }
+    println!("repeat: {}", client.get_status()?.repeat.as_str());
+    Ok(())
+}
 
-    // Fallback if position detection fails
-    show_detailed_status()?;
+/// Reorder the saved queue to match mpv's playlist, e.g. after mpv shuffled it
+fn sync_queue_order(client: &mut Mpv) -> anyhow::Result<()> {
+    let urls = client.playlist_urls()?;
+    let mut queue = Queue::load(queue_file())?;
+    if let Some(entries) = reorder_entries(queue.entries(), &urls) {
+        queue.replace(entries);
+        queue.save()?;
+    }
     Ok(())
 }
 
+/// Connect to the running player, if any, making sure its playlist is still the queue.
+/// mpv replaces a playlist URL with the videos in it, after which queue positions no
+/// longer name the same tracks in the player.
+fn player_matching_queue(queue: &Queue) -> anyhow::Result<Option<Mpv>> {
+    if !mpv::is_running() {
+        return Ok(None);
+    }
+    let mut client = Mpv::connect()?;
+    let urls = client.playlist_urls()?;
+    if !matches_playlist(queue.entries(), &urls) {
+        return Err(anyhow::anyhow!(
+            "The player's playlist ({} entries) no longer matches the queue ({} entries), \
+             as mpv expanded a playlist URL; change it from mpv, or restart with `ytm queue play`",
+            urls.len(),
+            queue.entries().len()
+        ));
+    }
+    Ok(Some(client))
+}
+
+/// Whether the queue lists the same URLs as mpv's playlist, in the same order
+fn matches_playlist(entries: &[QueueEntry], urls: &[String]) -> bool {
+    entries.len() == urls.len()
+        && entries
+            .iter()
+            .zip(urls)
+            .all(|(entry, url)| &entry.url == url)
+}
+
+/// The entries in the order of `urls`, or `None` if they aren't the same entries
+fn reorder_entries(entries: &[QueueEntry], urls: &[String]) -> Option<Vec<QueueEntry>> {
+    if entries.len() != urls.len() {
+        return None;
+    }
+    let mut remaining: Vec<Option<&QueueEntry>> = entries.iter().map(Some).collect();
+    urls.iter()
+        .map(|url| {
+            let slot = remaining
+                .iter_mut()
+                .find(|slot| slot.is_some_and(|entry| &entry.url == url))?;
+            slot.take().cloned()
+        })
+        .collect()
+}
+
+pub fn next() -> anyhow::Result<()> {
+    switch_track("playlist-next")
+}
+
+pub fn prev() -> anyhow::Result<()> {
+    switch_track("playlist-prev")
+}
+
+/// Send `playlist-next` or `playlist-prev`, then show the track mpv switches to
+fn switch_track(command: &str) -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    client.observe_property(1, "playlist-pos")?;
+    // Observing reports the current value right away
+    let before = client
+        .wait_for_event(&["property-change"], Duration::from_secs(1))?
+        .and_then(|event| event.get("data").cloned());
+    client.command(json!([command, "force"]))?;
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match client.wait_for_event(&["property-change"], remaining) {
+            Ok(Some(event)) if event.get("data") == before.as_ref() => {}
+            // Switched, timed out, or quit after the last track
+            Ok(_) => break,
+            Err(err) if mpv::is_disconnected(&err) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    // Done with it; the status is read over a connection of its own
+    drop(client);
+    show_detailed_status(StatusWait::Switch)
+}
+
 pub fn stop() -> anyhow::Result<()> {
-    use serde_json::json;
-    if mpv::send_mpv_command(json!({"command": ["stop"]})).is_ok() {
-        return Ok(());
+    // The watcher saves it too, but may not get to before the player quits
+    if let Ok(status) = Mpv::connect().and_then(|mut client| client.get_status()) {
+        if let (Some(video_id), Some(position)) = (status.video_id, status.position) {
+            save_position(Some(PlayedPosition {
+                video_id,
+                position,
+                duration: status.duration,
+            }))?;
+        }
+    }
+    match mpv::send_mpv_command(json!(["stop"])) {
+        Ok(()) => return Ok(()),
+        // mpv may quit before its reply makes it back to us
+        Err(err) if mpv::is_disconnected(&err) => return Ok(()),
+        Err(_) => {}
     }
     // Fallback to force kill
     mpv::force_kill()
 }
 
-pub fn status() -> anyhow::Result<()> {
+pub fn status(format: &StatusFormat, follow: bool) -> anyhow::Result<()> {
     if !mpv::is_running() {
-        println!("No player currently running");
+        match format {
+            StatusFormat::Text => println!("No player currently running"),
+            _ => eprintln!("No player currently running"),
+        }
+        std::process::exit(EXIT_NO_PLAYER);
+    }
+
+    if follow {
+        return follow_status(format);
+    }
+
+    match format {
+        StatusFormat::Text => show_detailed_status(StatusWait::No),
+        StatusFormat::Json => {
+            let status = player_status(&mut Mpv::connect()?)?;
+            println!("{}", serde_json::to_string(&status)?);
+            Ok(())
+        }
+        StatusFormat::Template(template) => {
+            let status = player_status(&mut Mpv::connect()?)?;
+            println!("{}", render_status(template, &status));
+            Ok(())
+        }
+    }
+}
+
+/// Print a status line whenever an observed property changes, until mpv quits
+fn follow_status(format: &StatusFormat) -> anyhow::Result<()> {
+    let mut client = Mpv::connect()?;
+    for (id, property) in FOLLOWED_PROPERTIES.iter().enumerate() {
+        client.observe_property(id as u64 + 1, property)?;
+    }
+
+    let render = |status: &MpvStatus| -> anyhow::Result<String> {
+        Ok(match format {
+            StatusFormat::Json => serde_json::to_string(status)?,
+            StatusFormat::Template(template) => render_status(template, status),
+            StatusFormat::Text => render_status(FOLLOW_TEMPLATE, status),
+        })
+    };
+
+    let mut last_line = String::new();
+    let mut last_position_update: Option<std::time::Instant> = None;
+    loop {
+        let event = match client.next_event() {
+            Ok(event) => event,
+            Err(err) if mpv::is_disconnected(&err) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        match event.get("event").and_then(|e| e.as_str()) {
+            Some("shutdown") => return Ok(()),
+            Some("property-change") => {}
+            _ => continue,
+        }
+
+        // Position changes arrive many times per second; report them at most once a second
+        if event.get("name").and_then(|n| n.as_str()) == Some("time-pos") {
+            if last_position_update.is_some_and(|at| at.elapsed() < Duration::from_secs(1)) {
+                continue;
+            }
+            last_position_update = Some(std::time::Instant::now());
+        }
+
+        let status = match player_status(&mut client) {
+            Ok(status) => status,
+            Err(err) if mpv::is_disconnected(&err) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let line = render(&status)?;
+        if line != last_line {
+            println!("{}", line);
+            last_line = line;
+        }
+    }
+}
+
+/// Queue entries for a URL, or for the search results the user picks
+async fn target_entries(
+    target: &str,
+    filters: &SearchFilters,
+    picker: Picker,
+    pick_tracks: bool,
+) -> anyhow::Result<Vec<QueueEntry>> {
+    if is_url(target) {
+        return Ok(vec![QueueEntry {
+            url: target.to_string(),
+            title: target.to_string(),
+            ..Default::default()
+        }]);
+    }
+    let picked = search_and_pick(target, filters, picker).await?;
+    resolve_entries(picked, pick_tracks.then_some(picker)).await
+}
+
+/// Add a URL, or picked search results, to the end of the queue
+pub async fn queue_add(
+    target: &str,
+    filters: &SearchFilters,
+    picker: Picker,
+    pick_tracks: bool,
+) -> anyhow::Result<()> {
+    let entries = target_entries(target, filters, picker, pick_tracks).await?;
+
+    let mut queue = Queue::load(queue_file())?;
+    let mut client = if mpv::is_running() {
+        Some(Mpv::connect()?)
+    } else {
+        None
+    };
+    for entry in entries {
+        if let Some(client) = client.as_mut() {
+            client.append(&entry.url, entry.media_title.as_deref())?;
+        }
+        println!("Queued #{}: {}", queue.entries().len() + 1, entry.title);
+        queue.push(entry);
+    }
+    queue.save()
+}
+
+pub fn queue_list() -> anyhow::Result<()> {
+    let queue = Queue::load(queue_file())?;
+    if queue.entries().is_empty() {
+        println!("Queue is empty");
+        return Ok(());
+    }
+
+    // Only marked while mpv's playlist positions are the queue's
+    let current = Mpv::connect().ok().and_then(|mut client| {
+        let urls = client.playlist_urls().ok()?;
+        matches_playlist(queue.entries(), &urls)
+            .then(|| current_playlist_pos(&mut client))
+            .flatten()
+    });
+    for (i, entry) in queue.entries().iter().enumerate() {
+        let marker = if current == Some(i) { ">" } else { " " };
+        println!("{} {:>3}. {}", marker, i + 1, entry.title);
+    }
+    Ok(())
+}
+
+pub fn queue_remove(n: usize) -> anyhow::Result<()> {
+    let mut queue = Queue::load(queue_file())?;
+    let player = player_matching_queue(&queue)?;
+    let removed = queue.remove(n)?;
+    if let Some(mut client) = player {
+        client.playlist_remove(n - 1)?;
+    }
+    queue.save()?;
+    println!("Removed #{}: {}", n, removed.title);
+    Ok(())
+}
+
+pub fn queue_move(from: usize, to: usize) -> anyhow::Result<()> {
+    let mut queue = Queue::load(queue_file())?;
+    let player = player_matching_queue(&queue)?;
+    queue.move_entry(from, to)?;
+    if let Some(mut client) = player {
+        client.playlist_move(from - 1, to - 1)?;
+    }
+    queue.save()
+}
+
+pub fn queue_clear() -> anyhow::Result<()> {
+    let mut queue = Queue::load(queue_file())?;
+
+    // mpv keeps the playing entry on playlist-clear, so the queue does too
+    let current = match player_matching_queue(&queue)? {
+        Some(mut client) => {
+            let current =
+                current_playlist_pos(&mut client).and_then(|i| queue.entries().get(i).cloned());
+            client.playlist_clear()?;
+            current
+        }
+        None => None,
+    };
+    queue.replace(current.into_iter().collect());
+    queue.save()
+}
+
+/// Start a new player with the saved queue
+pub fn queue_play(options: &PlayOptions) -> anyhow::Result<()> {
+    let queue = Queue::load(queue_file())?;
+    if queue.entries().is_empty() {
+        return Err(anyhow::anyhow!("Queue is empty"));
+    }
+    play_entries(queue.entries().to_vec(), options)
+}
+
+/// Name of the list `ytm fav add` adds to
+const FAVOURITES: &str = "favourites";
+
+fn lists_dir() -> PathBuf {
+    data_dir().join("lists")
+}
+
+/// Path of a named local list, rejecting names that would point outside the lists dir
+fn list_file(name: &str) -> anyhow::Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\0']) || name.starts_with('.') {
+        return Err(anyhow::anyhow!("Invalid list name '{}'", name));
+    }
+    Ok(lists_dir().join(format!("{name}.json")))
+}
+
+/// Load an existing local list
+fn load_list(name: &str) -> anyhow::Result<Queue> {
+    let path = list_file(name)?;
+    if !path.exists() {
+        return Err(anyhow::anyhow!(
+            "No list named '{}'; create it with 'ytm list create {}'",
+            name,
+            name
+        ));
+    }
+    Queue::load(path)
+}
+
+/// Names of the local lists, sorted
+fn list_names() -> anyhow::Result<Vec<String>> {
+    let mut names: Vec<String> = match std::fs::read_dir(lists_dir()) {
+        Ok(dir) => dir
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_string_lossy().into_owned())
+            })
+            .collect(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+    names.sort();
+    Ok(names)
+}
+
+/// Queue entry for the track the player is playing
+fn current_entry() -> anyhow::Result<QueueEntry> {
+    let status = Mpv::connect()?.get_status()?;
+    let url = status
+        .url
+        .ok_or_else(|| anyhow::anyhow!("Nothing is playing"))?;
+    let title = status.title.unwrap_or_else(|| url.clone());
+    Ok(QueueEntry {
+        title: match &status.channel {
+            Some(channel) => format!("{} | {}", title, channel),
+            None => title.clone(),
+        },
+        media_title: Some(title),
+        url,
+        playlist: None,
+    })
+}
+
+/// Add the playing track to the favourites list
+pub fn fav_add() -> anyhow::Result<()> {
+    let entry = current_entry()?;
+    let mut list = Queue::load(list_file(FAVOURITES)?)?;
+    if list.entries().iter().any(|e| e.url == entry.url) {
+        println!("Already in {}: {}", FAVOURITES, entry.title);
         return Ok(());
     }
+    println!("Added to {}: {}", FAVOURITES, entry.title);
+    list.push(entry);
+    list.save()
+}
+
+pub fn list_create(name: &str) -> anyhow::Result<()> {
+    let path = list_file(name)?;
+    if path.exists() {
+        return Err(anyhow::anyhow!("List '{}' already exists", name));
+    }
+    Queue::load(path)?.save()?;
+    println!("Created list '{}'", name);
+    Ok(())
+}
+
+/// Add the playing track, a URL, or picked search results to a list
+pub async fn list_add(
+    name: &str,
+    target: Option<&str>,
+    filters: &SearchFilters,
+    picker: Picker,
+    pick_tracks: bool,
+) -> anyhow::Result<()> {
+    let mut list = load_list(name)?;
+    let entries = match target {
+        Some(target) => target_entries(target, filters, picker, pick_tracks).await?,
+        None => vec![current_entry()?],
+    };
+    for entry in entries {
+        println!(
+            "Added to {} #{}: {}",
+            name,
+            list.entries().len() + 1,
+            entry.title
+        );
+        list.push(entry);
+    }
+    list.save()
+}
 
-    show_detailed_status()
+pub fn list_remove(name: &str, n: usize) -> anyhow::Result<()> {
+    let mut list = load_list(name)?;
+    let removed = list.remove(n)?;
+    list.save()?;
+    println!("Removed from {}: {}", name, removed.title);
+    Ok(())
+}
+
+/// Show the entries of a list, or all lists without a name
+pub fn list_show(name: Option<&str>) -> anyhow::Result<()> {
+    let Some(name) = name else {
+        let names = list_names()?;
+        if names.is_empty() {
+            println!("No lists yet; create one with 'ytm list create <name>'");
+        }
+        for name in names {
+            println!("{} ({} tracks)", name, load_list(&name)?.entries().len());
+        }
+        return Ok(());
+    };
+
+    let list = load_list(name)?;
+    if list.entries().is_empty() {
+        println!("List '{}' is empty", name);
+    }
+    for (i, entry) in list.entries().iter().enumerate() {
+        println!("{:>3}. {}", i + 1, entry.title);
+    }
+    Ok(())
+}
+
+/// Play a list, picked interactively without a name. With `pick_tracks`, pick which
+/// of its tracks to play.
+pub fn list_play(
+    name: Option<&str>,
+    picker: Picker,
+    pick_tracks: bool,
+    options: &PlayOptions,
+) -> anyhow::Result<()> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => {
+            let names = list_names()?;
+            let entries: Vec<PickerEntry> = names
+                .iter()
+                .map(|name| PickerEntry {
+                    key: format!("list:{name}"),
+                    label: name.clone(),
+                })
+                .collect();
+            match picker.pick(&entries, &PickOptions::default())?.first() {
+                Some(&index) => names[index].clone(),
+                None => return Ok(()),
+            }
+        }
+    };
+
+    let tracks = load_list(&name)?.entries().to_vec();
+    if tracks.is_empty() {
+        return Err(anyhow::anyhow!("List '{}' is empty", name));
+    }
+    let tracks = if pick_tracks {
+        let entries: Vec<PickerEntry> = tracks
+            .iter()
+            .map(|track| PickerEntry {
+                key: track.url.clone(),
+                label: track.title.clone(),
+            })
+            .collect();
+        let options = PickOptions {
+            multi: true,
+            preview: None,
+        };
+        let picked = picker.pick(&entries, &options)?;
+        select_tracks(tracks, &picked)
+    } else {
+        tracks
+    };
+    if tracks.is_empty() {
+        return Ok(());
+    }
+    play_entries(tracks, options)
+}
+
+/// Format a history timestamp as local date and time
+fn format_played_at(played_at: i64) -> String {
+    chrono::DateTime::from_timestamp(played_at, 0)
+        .map(|time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// One line of `ytm history`: when, what, and how long it was listened to
+fn history_label(entry: &HistoryEntry) -> String {
+    let mut label = format!("{}  {}", format_played_at(entry.played_at), entry.title);
+    if let Some(channel) = &entry.channel {
+        label.push_str(&format!(" | {}", channel));
+    }
+    label.push_str(&format!(" ({})", format_duration(entry.listened as u32)));
+    label
+}
+
+/// Show the last `limit` played tracks, newest first, or pick some of them to play again
+pub fn history(
+    limit: usize,
+    json: bool,
+    replay: Option<Picker>,
+    options: &PlayOptions,
+) -> anyhow::Result<()> {
+    let mut entries = History::new(history_file()).load()?;
+    entries.reverse();
+    entries.truncate(limit);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        println!("Nothing played yet");
+        return Ok(());
+    }
+
+    let Some(picker) = replay else {
+        for entry in &entries {
+            println!("{}", history_label(entry));
+        }
+        return Ok(());
+    };
+
+    let picker_entries: Vec<PickerEntry> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| PickerEntry {
+            key: format!("history:{i}"),
+            label: history_label(entry),
+        })
+        .collect();
+    let pick_options = PickOptions {
+        multi: true,
+        preview: None,
+    };
+    let picked: Vec<QueueEntry> = picker
+        .pick(&picker_entries, &pick_options)?
+        .into_iter()
+        .map(|index| {
+            let entry = &entries[index];
+            QueueEntry {
+                url: entry.url.clone(),
+                title: match &entry.channel {
+                    Some(channel) => format!("{} | {}", entry.title, channel),
+                    None => entry.title.clone(),
+                },
+                media_title: Some(entry.title.clone()),
+                playlist: None,
+            }
+        })
+        .collect();
+    if picked.is_empty() {
+        return Ok(());
+    }
+    play_entries(picked, options)
+}
+
+/// 0-based index of the entry mpv is currently playing
+fn current_playlist_pos(client: &mut Mpv) -> Option<usize> {
+    client
+        .get_property("playlist-pos")
+        .ok()
+        .flatten()
+        .and_then(|v| v.as_u64())
+        .map(|pos| pos as usize)
+}
+
+/// Print the effective settings as TOML
+pub fn config_show(path: &Path, profile: Option<&str>) -> anyhow::Result<()> {
+    let mut settings = config::current().clone();
+    let session = session::current();
+    settings.session = Some(session.name().to_string());
+    settings.socket = Some(session.socket().to_path_buf());
+
+    let missing = if path.exists() { "" } else { " (not found)" };
+    println!("# config file: {}{}", path.display(), missing);
+    if let Some(profile) = profile {
+        println!("# profile: {}", profile);
+    }
+    print!("{}", toml::to_string(&settings)?);
+    Ok(())
+}
+
+pub fn config_path(path: &Path) -> anyhow::Result<()> {
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Open the config file in the user's editor, starting from a commented template
+pub fn config_edit(path: &Path) -> anyhow::Result<()> {
+    if !path.exists() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, config::TEMPLATE)?;
+    }
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // Through the shell, as the editor may come with arguments, e.g. "code --wait"
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("{} exited with {}", editor, status));
+    }
+    // Point out mistakes right away rather than on the next run
+    let file = config::ConfigFile::load(path)?;
+    config::Settings::resolve(file.layer(None)?)?;
+    Ok(())
+}
+
+/// Check that everything ytm needs is installed and usable
+pub fn doctor(picker: Picker) -> anyhow::Result<()> {
+    doctor::run(&cache_dir(), &data_dir(), picker)
 }
 
 #[cfg(test)]
@@ -327,4 +1891,237 @@ mod tests {
         assert_eq!(format_time(Some(125.5)), "02:06");
         assert_eq!(format_time(None), "--:--");
     }
+
+    #[test]
+    fn test_render_status() {
+        let status = MpvStatus {
+            title: Some("Roads".to_string()),
+            channel: Some("Portishead".to_string()),
+            position: Some(65.0),
+            duration: Some(300.0),
+            paused: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render_status("{title} — {pos}/{dur} [{state}]", &status),
+            "Roads — 01:05/05:00 [paused]"
+        );
+        assert_eq!(
+            render_status("{channel}: {album}{unknown} {percent}%", &status),
+            "Portishead: {unknown} 21%"
+        );
+        assert_eq!(render_status("{title", &status), "{title");
+        assert_eq!(render_status("{shuffle}/{repeat}", &status), "off/off");
+    }
+
+    #[test]
+    fn test_search_row_tsv() {
+        let item = SearchItem {
+            id: crate::api::ItemId {
+                kind: "youtube#video".to_string(),
+                video_id: Some("abc123".to_string()),
+                ..Default::default()
+            },
+            snippet: crate::api::Snippet {
+                title: "Roads\t(Live)".to_string(),
+                channel_title: "Portishead".to_string(),
+                description: Some("Line one\nLine two".to_string()),
+                ..Default::default()
+            },
+        };
+
+        let row = SearchRow::from_item(&item).unwrap();
+        assert_eq!(
+            row.to_tsv(),
+            "abc123\tvideo\tRoads (Live)\tPortishead\tLine one Line two\thttps://www.youtube.com/watch?v=abc123"
+        );
+    }
+
+    #[test]
+    fn test_choice_labels_show_duration_and_markers() {
+        let video = |duration, is_live, is_short| SearchItem {
+            id: crate::api::ItemId {
+                kind: "youtube#video".to_string(),
+                video_id: Some("abc123".to_string()),
+                ..Default::default()
+            },
+            snippet: crate::api::Snippet {
+                title: "Windowlicker".to_string(),
+                channel_title: "Aphex Twin".to_string(),
+                duration,
+                is_live,
+                is_short,
+                ..Default::default()
+            },
+        };
+        let label = |item: SearchItem| SearchChoice::from_item(&item).unwrap().label;
+
+        assert_eq!(
+            label(video(Some(367), false, false)),
+            "Windowlicker | Aphex Twin [6:07]"
+        );
+        assert_eq!(
+            label(video(Some(3723), false, false)),
+            "Windowlicker | Aphex Twin [1:02:03]"
+        );
+        assert_eq!(
+            label(video(None, true, false)),
+            "Windowlicker | Aphex Twin [LIVE]"
+        );
+        assert_eq!(
+            label(video(Some(45), false, true)),
+            "Windowlicker | Aphex Twin [0:45] [SHORT]"
+        );
+    }
+
+    #[test]
+    fn test_music_choices_map_to_music_targets() {
+        let item = |kind: &str, id: crate::api::ItemId| SearchItem {
+            id: crate::api::ItemId {
+                kind: kind.to_string(),
+                ..id
+            },
+            snippet: crate::api::Snippet {
+                title: "Dummy".to_string(),
+                channel_title: "Portishead".to_string(),
+                description: Some("Album · 1994".to_string()),
+                ..Default::default()
+            },
+        };
+
+        let album = SearchChoice::from_item(&item(
+            "ytmusic#album",
+            crate::api::ItemId {
+                album_id: Some("MPREb_dummy".to_string()),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+        assert_eq!(album.target, SearchTarget::Album("MPREb_dummy".to_string()));
+        assert_eq!(album.target.key(), "album:MPREb_dummy");
+        assert_eq!(album.label, "Dummy | Portishead [Album · 1994]");
+
+        let song = SearchChoice::from_item(&item(
+            "ytmusic#track",
+            crate::api::ItemId {
+                video_id: Some("track123".to_string()),
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+        assert_eq!(
+            song.target.url(),
+            "https://music.youtube.com/watch?v=track123"
+        );
+    }
+
+    #[test]
+    fn test_select_tracks_and_entry_args() {
+        let tracks: Vec<QueueEntry> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| QueueEntry {
+                url: format!("https://www.youtube.com/watch?v={name}"),
+                title: name.to_string(),
+                media_title: Some(format!("Track {name}")),
+                playlist: Some("Mix".to_string()),
+            })
+            .collect();
+        let titles = |entries: Vec<QueueEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.title).collect()
+        };
+
+        assert_eq!(titles(select_tracks(tracks.clone(), &[2])), ["c", "d"]);
+        assert_eq!(titles(select_tracks(tracks.clone(), &[3, 0])), ["d", "a"]);
+        assert!(select_tracks(tracks.clone(), &[]).is_empty());
+
+        assert_eq!(
+            entry_args(&tracks[0], None),
+            [
+                "--{",
+                "--force-media-title=Track a",
+                "https://www.youtube.com/watch?v=a",
+                "--}"
+            ]
+        );
+        let plain = QueueEntry {
+            media_title: None,
+            ..tracks[1].clone()
+        };
+        assert_eq!(
+            entry_args(&plain, None),
+            ["https://www.youtube.com/watch?v=b"]
+        );
+        assert_eq!(
+            entry_args(&plain, Some(1834.6)),
+            [
+                "--{",
+                "--start=1835",
+                "https://www.youtube.com/watch?v=b",
+                "--}"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reorder_entries_follows_mpv_playlist() {
+        let entry = |name: &str| QueueEntry {
+            url: format!("https://www.youtube.com/watch?v={name}"),
+            title: name.to_string(),
+            ..Default::default()
+        };
+        let entries = [entry("a"), entry("b"), entry("a"), entry("c")];
+        let urls =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| entry(name).url).collect() };
+
+        let reordered = reorder_entries(&entries, &urls(&["c", "a", "b", "a"])).unwrap();
+        assert_eq!(reordered, [entry("c"), entry("a"), entry("b"), entry("a")]);
+
+        assert!(reorder_entries(&entries, &urls(&["c", "b", "b", "a"])).is_none());
+        assert!(reorder_entries(&entries, &urls(&["a", "b", "c"])).is_none());
+
+        assert!(matches_playlist(&entries, &urls(&["a", "b", "a", "c"])));
+        assert!(!matches_playlist(&entries, &urls(&["c", "a", "b", "a"])));
+        assert!(!matches_playlist(
+            &entries,
+            &urls(&["a", "b", "a", "c1", "c2"])
+        ));
+    }
+
+    #[test]
+    fn test_list_names_stay_in_lists_dir() {
+        let path = list_file("road trip").unwrap();
+        assert_eq!(path.file_name().unwrap(), "road trip.json");
+        assert_eq!(path.parent().unwrap(), lists_dir());
+
+        for name in ["", "../queue", "a/b", ".hidden"] {
+            assert!(list_file(name).is_err(), "{name:?} accepted");
+        }
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/usr/bin/ytm"), "'/usr/bin/ytm'");
+        assert_eq!(shell_quote("/home/o'neil/ytm"), "'/home/o'\\''neil/ytm'");
+    }
+
+    #[test]
+    fn test_parse_seek() {
+        assert_eq!(parse_seek("+10").unwrap(), (10.0, "relative"));
+        assert_eq!(parse_seek("-1:30").unwrap(), (-90.0, "relative"));
+        assert_eq!(parse_seek("90").unwrap(), (90.0, "absolute"));
+        assert_eq!(parse_seek("1:02:03").unwrap(), (3723.0, "absolute"));
+        assert_eq!(parse_seek("50%").unwrap(), (50.0, "absolute-percent"));
+        assert_eq!(parse_seek("-5%").unwrap(), (-5.0, "relative-percent"));
+        assert!(parse_seek("abc").is_err());
+        assert!(parse_seek("1:-30").is_err());
+    }
+
+    #[test]
+    fn test_parse_volume() {
+        assert_eq!(parse_volume("70").unwrap(), VolumeChange::Set(70.0));
+        assert_eq!(parse_volume("+5").unwrap(), VolumeChange::Adjust(5.0));
+        assert_eq!(parse_volume("-10").unwrap(), VolumeChange::Adjust(-10.0));
+        assert!(parse_volume("loud").is_err());
+    }
 }
@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::picker::Picker;
+
+/// Most search results rustypipe is asked for
+const MAX_RESULTS: u32 = 50;
+
+static CURRENT: OnceLock<Settings> = OnceLock::new();
+
+/// Written by `ytm config edit` when there is no config file yet
+pub const TEMPLATE: &str = r#"# ytm settings. Command-line flags and YTM_* environment variables take precedence.
+# Uncomment a line to change its default.
+
+# video = false                             # open a video window, like -v
+# background = false                        # detach the player, like -b
+# music = false                             # search YouTube Music, like -m
+# picker = "fzf"                            # fzf, skim, builtin or none
+# results = 50                              # search results to fetch, at most 50
+# cache-ttl = 3600                          # seconds a search stays cached
+# quality = "high"                          # low, medium, high, opus-only, 720p or 1080p
+# audio-format = "bestaudio/best"           # yt-dlp format for audio-only playback, without quality
+# video-format = "bestvideo+bestaudio/best" # yt-dlp format with -v, without quality
+# ytdl-format = "bestaudio"                 # yt-dlp format, overriding all of the above
+# player-client = "android"                 # yt-dlp YouTube player_client; "" for yt-dlp's choice
+# extractor-args = "youtube:lang=en"        # yt-dlp extractor-args instead of player-client
+# mpv-args = ["--af=loudnorm", "--audio-device=pulse/headphones"]
+# session = "default"
+# socket = "/run/user/1000/ytm/default.sock"
+
+# Profiles override the settings above when picked with --profile or YTM_PROFILE.
+# profile = "work"                          # profile to use when none is picked
+#
+# [profiles.work]
+# music = true
+# picker = "builtin"
+"#;
+
+/// Named stream quality, turned into a yt-dlp format selector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quality {
+    /// About 64 kbit/s audio and 360p video, for metered connections
+    Low,
+    /// About 128 kbit/s audio and 480p video
+    Medium,
+    /// The best streams available
+    High,
+    /// Opus audio only, failing rather than falling back to another codec
+    OpusOnly,
+    /// Video of at most 720p; audio-only playback takes the best audio
+    #[value(name = "720p")]
+    #[serde(rename = "720p")]
+    P720,
+    /// Video of at most 1080p; audio-only playback takes the best audio
+    #[value(name = "1080p")]
+    #[serde(rename = "1080p")]
+    P1080,
+}
+
+impl Quality {
+    /// yt-dlp format selector for audio-only or video playback
+    pub fn format(self, no_video: bool) -> &'static str {
+        match (self, no_video) {
+            (Quality::Low, true) => "bestaudio[abr<=64]/worstaudio/worst",
+            (Quality::Low, false) => {
+                "bestvideo[height<=360]+bestaudio[abr<=64]/best[height<=360]/worst"
+            }
+            (Quality::Medium, true) => "bestaudio[abr<=128]/bestaudio/best",
+            (Quality::Medium, false) => {
+                "bestvideo[height<=480]+bestaudio[abr<=128]/best[height<=480]/best"
+            }
+            (Quality::High | Quality::P720 | Quality::P1080, true) => "bestaudio/best",
+            (Quality::High, false) => "bestvideo+bestaudio/best",
+            (Quality::OpusOnly, true) => "bestaudio[acodec=opus]",
+            (Quality::OpusOnly, false) => "bestvideo+bestaudio[acodec=opus]",
+            (Quality::P720, false) => "bestvideo[height<=720]+bestaudio/best[height<=720]/best",
+            (Quality::P1080, false) => "bestvideo[height<=1080]+bestaudio/best[height<=1080]/best",
+        }
+    }
+}
+
+/// Settings from one source; the ones left unset fall through to the next source
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Layer {
+    pub video: Option<bool>,
+    pub background: Option<bool>,
+    pub music: Option<bool>,
+    pub picker: Option<Picker>,
+    pub results: Option<u32>,
+    pub cache_ttl: Option<u64>,
+    pub quality: Option<Quality>,
+    pub audio_format: Option<String>,
+    pub video_format: Option<String>,
+    pub ytdl_format: Option<String>,
+    pub player_client: Option<String>,
+    pub extractor_args: Option<String>,
+    pub mpv_args: Option<Vec<String>>,
+    pub session: Option<String>,
+    pub socket: Option<PathBuf>,
+}
+
+impl Layer {
+    /// Take the settings unset here from `fallback`
+    pub fn or(self, fallback: Layer) -> Layer {
+        // A quality picked here beats a format selector from further down
+        let fallback_format = if self.quality.is_some() {
+            None
+        } else {
+            fallback.ytdl_format
+        };
+        Layer {
+            video: self.video.or(fallback.video),
+            background: self.background.or(fallback.background),
+            music: self.music.or(fallback.music),
+            picker: self.picker.or(fallback.picker),
+            results: self.results.or(fallback.results),
+            cache_ttl: self.cache_ttl.or(fallback.cache_ttl),
+            quality: self.quality.or(fallback.quality),
+            audio_format: self.audio_format.or(fallback.audio_format),
+            video_format: self.video_format.or(fallback.video_format),
+            ytdl_format: self.ytdl_format.or(fallback_format),
+            player_client: self.player_client.or(fallback.player_client),
+            extractor_args: self.extractor_args.or(fallback.extractor_args),
+            mpv_args: self.mpv_args.or(fallback.mpv_args),
+            session: self.session.or(fallback.session),
+            socket: self.socket.or(fallback.socket),
+        }
+    }
+}
+
+/// Effective settings, once the command line, environment and config file are merged
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Settings {
+    pub video: bool,
+    pub background: bool,
+    pub music: bool,
+    pub picker: Picker,
+    pub results: u32,
+    /// Seconds a search result stays cached
+    pub cache_ttl: u64,
+    pub quality: Option<Quality>,
+    pub audio_format: String,
+    pub video_format: String,
+    /// Format selector used as is, whatever the quality and video mode
+    pub ytdl_format: Option<String>,
+    pub player_client: String,
+    /// Replaces the extractor-args built from `player_client`; empty to pass none
+    pub extractor_args: Option<String>,
+    /// Extra options for every mpv started, before those given after `--`
+    pub mpv_args: Vec<String>,
+    pub session: Option<String>,
+    pub socket: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Fill whatever `layer` leaves unset with the built-in defaults
+    pub fn resolve(layer: Layer) -> Result<Self> {
+        let results = layer.results.unwrap_or(MAX_RESULTS);
+        if !(1..=MAX_RESULTS).contains(&results) {
+            return Err(anyhow::anyhow!(
+                "results must be between 1 and {}, not {}",
+                MAX_RESULTS,
+                results
+            ));
+        }
+        Ok(Self {
+            video: layer.video.unwrap_or(false),
+            background: layer.background.unwrap_or(false),
+            music: layer.music.unwrap_or(false),
+            picker: layer.picker.unwrap_or_else(Picker::detect),
+            results,
+            cache_ttl: layer.cache_ttl.unwrap_or(3600),
+            quality: layer.quality,
+            audio_format: layer
+                .audio_format
+                .unwrap_or_else(|| "bestaudio/best".to_string()),
+            // Let yt-dlp pick the best adaptive combo, falling back to a single progressive stream
+            video_format: layer
+                .video_format
+                .unwrap_or_else(|| "bestvideo+bestaudio/best".to_string()),
+            // The Android client profile prefers AVC/MP4 streams
+            player_client: layer.player_client.unwrap_or_else(|| "android".to_string()),
+            ytdl_format: layer.ytdl_format,
+            extractor_args: layer.extractor_args,
+            mpv_args: layer.mpv_args.unwrap_or_default(),
+            session: layer.session,
+            socket: layer.socket,
+        })
+    }
+
+    /// yt-dlp format selector: `ytdl_format` if set, else the quality's, else the configured
+    /// audio or video format
+    pub fn format(&self, no_video: bool) -> &str {
+        match (&self.ytdl_format, self.quality) {
+            (Some(format), _) => format,
+            (None, Some(quality)) => quality.format(no_video),
+            (None, None) if no_video => &self.audio_format,
+            (None, None) => &self.video_format,
+        }
+    }
+
+    /// yt-dlp extractor-args, if any are to be passed
+    pub fn extractor_args(&self) -> Option<String> {
+        match &self.extractor_args {
+            Some(args) => (!args.is_empty()).then(|| args.clone()),
+            None if self.player_client.is_empty() => None,
+            None => Some(format!("youtube:player_client={}", self.player_client)),
+        }
+    }
+}
+
+/// The config file: top-level settings, and named profiles overriding them
+#[derive(Debug, Default)]
+pub struct ConfigFile {
+    base: Layer,
+    /// Profile used when none is picked on the command line
+    default_profile: Option<String>,
+    profiles: BTreeMap<String, Layer>,
+}
+
+impl ConfigFile {
+    /// Read the config file at `path`; a missing file is the same as an empty one
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(raw) => Self::parse(&raw).with_context(|| format!("invalid config file {:?}", path)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("failed to read config file {:?}", path)),
+        }
+    }
+
+    fn parse(raw: &str) -> Result<Self> {
+        let mut table: toml::Table = raw.parse()?;
+        let default_profile = match table.remove("profile") {
+            Some(toml::Value::String(name)) => Some(name),
+            Some(_) => return Err(anyhow::anyhow!("profile must be a profile name")),
+            None => None,
+        };
+        let profiles = match table.remove("profiles") {
+            Some(profiles) => profiles.try_into()?,
+            None => BTreeMap::new(),
+        };
+        Ok(Self {
+            base: table.try_into()?,
+            default_profile,
+            profiles,
+        })
+    }
+
+    /// Name of the profile in effect: `profile` if given, else the file's default one
+    pub fn profile<'a>(&'a self, profile: Option<&'a str>) -> Option<&'a str> {
+        profile.or(self.default_profile.as_deref())
+    }
+
+    /// Settings of the profile in effect on top of the top-level ones
+    pub fn layer(&self, profile: Option<&str>) -> Result<Layer> {
+        let Some(name) = self.profile(profile) else {
+            return Ok(self.base.clone());
+        };
+        match self.profiles.get(name) {
+            Some(layer) => Ok(layer.clone().or(self.base.clone())),
+            None => Err(anyhow::anyhow!(
+                "No profile '{}' in the config file (profiles: {})",
+                name,
+                if self.profiles.is_empty() {
+                    "none".to_string()
+                } else {
+                    self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                }
+            )),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/ytm/config.toml`
+pub fn default_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ytm")
+        .join("config.toml")
+}
+
+/// Select the settings used by every command in this process. Call once, at startup.
+pub fn init(settings: Settings) -> Result<()> {
+    CURRENT
+        .set(settings)
+        .map_err(|_| anyhow::anyhow!("config already initialized"))
+}
+
+/// The settings given to [`init`], or the built-in defaults
+pub fn current() -> &'static Settings {
+    CURRENT.get_or_init(|| Settings::resolve(Layer::default()).expect("defaults are valid"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_overrides_top_level_settings() {
+        let file = ConfigFile::parse(
+            r#"
+            results = 20
+            picker = "skim"
+            profile = "work"
+
+            [profiles.work]
+            music = true
+            picker = "builtin"
+            "#,
+        )
+        .unwrap();
+
+        let cli = Layer {
+            picker: Some(Picker::None),
+            ..Default::default()
+        };
+        let settings = Settings::resolve(cli.or(file.layer(None).unwrap())).unwrap();
+        assert_eq!(settings.picker, Picker::None);
+        assert!(settings.music);
+        assert_eq!(settings.results, 20);
+        assert_eq!(settings.cache_ttl, 3600);
+
+        assert_eq!(file.layer(None).unwrap().picker, Some(Picker::Builtin));
+        assert!(file.layer(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_rejects_unknown_and_invalid_settings() {
+        assert!(ConfigFile::parse("resluts = 10").is_err());
+        assert!(ConfigFile::parse("[profiles.work]\nvideo = \"yes\"").is_err());
+
+        let layer = ConfigFile::parse("results = 80")
+            .unwrap()
+            .layer(None)
+            .unwrap();
+        assert!(Settings::resolve(layer).is_err());
+    }
+
+    #[test]
+    fn test_format_and_extractor_args_precedence() {
+        let mut settings = Settings::resolve(Layer::default()).unwrap();
+        assert_eq!(settings.format(true), "bestaudio/best");
+        assert_eq!(
+            settings.extractor_args().as_deref(),
+            Some("youtube:player_client=android")
+        );
+
+        settings.quality = Some(Quality::P1080);
+        assert_eq!(settings.format(true), "bestaudio/best");
+        assert!(settings
+            .format(false)
+            .starts_with("bestvideo[height<=1080]+"));
+        settings.ytdl_format = Some("18".to_string());
+        assert_eq!(settings.format(false), "18");
+
+        settings.extractor_args = Some(String::new());
+        assert_eq!(settings.extractor_args(), None);
+    }
+
+    #[test]
+    fn test_cli_quality_beats_config_ytdl_format() {
+        let file = ConfigFile::parse("ytdl-format = \"bestaudio\"").unwrap();
+        let cli = Layer {
+            quality: Some(Quality::Low),
+            ..Default::default()
+        };
+        let settings = Settings::resolve(cli.or(file.layer(None).unwrap())).unwrap();
+        assert_eq!(settings.format(true), Quality::Low.format(true));
+
+        let settings = Settings::resolve(Layer::default().or(file.layer(None).unwrap())).unwrap();
+        assert_eq!(settings.format(true), "bestaudio");
+    }
+
+    #[test]
+    fn test_template_is_a_valid_empty_config() {
+        let file = ConfigFile::parse(TEMPLATE).unwrap();
+        assert_eq!(file.layer(None).unwrap(), Layer::default());
+    }
+}
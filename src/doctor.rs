@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+
+use crate::mpv;
+use crate::picker::{find_in_path, Picker};
+use crate::session;
+
+/// Pointer added to errors caused by a broken setup
+pub const HINT: &str = "run `ytm doctor` to check the setup";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Result of one check, printed as a line of the report
+struct Check {
+    level: Level,
+    name: &'static str,
+    detail: String,
+}
+
+impl Check {
+    fn new(level: Level, name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            level,
+            name,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Check the programs ytm runs and the directories it uses, printing one line per check.
+/// Fails when anything would stop playback from working.
+pub fn run(cache_dir: &Path, data_dir: &Path, picker: Picker) -> Result<()> {
+    let checks = [
+        program("mpv", "needed to play anything"),
+        program("yt-dlp", "mpv needs it to play YouTube"),
+        picker_program(picker),
+        mpv_ipc(),
+        socket_dir(),
+        writable_dir("cache dir", cache_dir),
+        writable_dir("data dir", data_dir),
+        rustypipe_storage(&cache_dir.join("rustypipe")),
+    ];
+
+    for check in &checks {
+        let level = match check.level {
+            Level::Ok => "ok",
+            Level::Warn => "warn",
+            Level::Fail => "FAIL",
+        };
+        println!("{:<5} {:<17} {}", level, check.name, check.detail);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.level == Level::Fail)
+        .count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} checks failed",
+            failed,
+            checks.len()
+        ));
+    }
+    Ok(())
+}
+
+/// First line of `program --version`, without mpv's copyright notice
+fn version(program: &str) -> Option<String> {
+    let output = Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next()?;
+    Some(
+        line.split(" Copyright")
+            .next()
+            .unwrap_or(line)
+            .trim()
+            .to_string(),
+    )
+}
+
+/// A program found on PATH, with its version
+fn found(name: &'static str, program: &str, path: &Path) -> Check {
+    let version = version(program).unwrap_or_else(|| "unknown version".to_string());
+    Check::new(Level::Ok, name, format!("{} ({})", version, path.display()))
+}
+
+fn program(name: &'static str, needed_for: &str) -> Check {
+    match find_in_path(name) {
+        Some(path) => found(name, name, &path),
+        None => Check::new(
+            Level::Fail,
+            name,
+            format!("not found on PATH; {}", needed_for),
+        ),
+    }
+}
+
+/// The external picker in use. A missing fzf is fine as long as nobody asked for it.
+fn picker_program(picker: Picker) -> Check {
+    let (program, name) = match picker {
+        Picker::Skim => ("sk", "skim"),
+        _ => ("fzf", "fzf"),
+    };
+    match (find_in_path(program), picker) {
+        (Some(path), _) => found(name, program, &path),
+        (None, Picker::Fzf | Picker::Skim) => Check::new(
+            Level::Fail,
+            name,
+            "not found on PATH; install it or use --picker builtin",
+        ),
+        (None, Picker::Builtin) => Check::new(
+            Level::Warn,
+            name,
+            "not found on PATH; using the built-in picker",
+        ),
+        (None, Picker::None) => Check::new(Level::Ok, name, "not needed with --picker none"),
+    }
+}
+
+/// ytm controls players through mpv's JSON IPC socket
+fn mpv_ipc() -> Check {
+    let output = Command::new("mpv")
+        .arg("--list-options")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if String::from_utf8_lossy(&output.stdout).contains("--input-ipc-server") => {
+            Check::new(Level::Ok, "mpv IPC", "--input-ipc-server supported")
+        }
+        Ok(_) => Check::new(
+            Level::Fail,
+            "mpv IPC",
+            "mpv lacks --input-ipc-server; -b and player controls won't work",
+        ),
+        Err(_) => Check::new(Level::Fail, "mpv IPC", "could not run mpv"),
+    }
+}
+
+/// The session's socket dir, and whether a player is listening on its socket
+fn socket_dir() -> Check {
+    let session = session::current();
+    let socket = session.socket();
+    if let Err(err) = session.check_socket_dir() {
+        return Check::new(Level::Fail, "socket", format!("{:#}", err));
+    }
+    if mpv::is_running() {
+        Check::new(
+            Level::Ok,
+            "socket",
+            format!("{} (player running)", socket.display()),
+        )
+    } else if socket.exists() {
+        Check::new(
+            Level::Warn,
+            "socket",
+            format!(
+                "{} is left over from a player that is gone; `ytm stop` removes it",
+                socket.display()
+            ),
+        )
+    } else {
+        Check::new(
+            Level::Ok,
+            "socket",
+            format!("{} (no player running)", socket.display()),
+        )
+    }
+}
+
+/// Create `dir` if needed and make sure files can be written in it
+fn ensure_writable(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(".ytm-doctor");
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)?;
+    Ok(())
+}
+
+fn writable_dir(name: &'static str, dir: &Path) -> Check {
+    match ensure_writable(dir) {
+        Ok(()) => Check::new(Level::Ok, name, dir.display().to_string()),
+        Err(err) => Check::new(
+            Level::Fail,
+            name,
+            format!("{} is not writable: {}", dir.display(), err),
+        ),
+    }
+}
+
+/// rustypipe keeps client versions and deobfuscation data in a JSON file; a corrupt one
+/// breaks searches
+fn rustypipe_storage(dir: &Path) -> Check {
+    if let Err(err) = ensure_writable(dir) {
+        return Check::new(
+            Level::Fail,
+            "rustypipe",
+            format!("{} is not writable: {}", dir.display(), err),
+        );
+    }
+    let cache_file = dir.join("rustypipe_cache.json");
+    match fs::read(&cache_file) {
+        Ok(raw) if serde_json::from_slice::<serde_json::Value>(&raw).is_ok() => {
+            Check::new(Level::Ok, "rustypipe", cache_file.display().to_string())
+        }
+        Ok(_) => Check::new(
+            Level::Fail,
+            "rustypipe",
+            format!("{} is corrupt; delete it", cache_file.display()),
+        ),
+        Err(_) => Check::new(
+            Level::Ok,
+            "rustypipe",
+            format!("{} (created on the first search)", dir.display()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rustypipe_storage_spots_corrupt_cache() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("rustypipe");
+        assert_eq!(rustypipe_storage(&dir).level, Level::Ok);
+
+        fs::write(dir.join("rustypipe_cache.json"), "{\"desktop_client\":").unwrap();
+        assert_eq!(rustypipe_storage(&dir).level, Level::Fail);
+        fs::write(dir.join("rustypipe_cache.json"), "{}").unwrap();
+        assert_eq!(rustypipe_storage(&dir).level, Level::Ok);
+    }
+}
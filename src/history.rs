@@ -0,0 +1,188 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// One track that was played
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub video_id: Option<String>,
+    pub url: String,
+    pub title: String,
+    pub channel: Option<String>,
+    /// When playback started, in seconds since the Unix epoch
+    pub played_at: i64,
+    /// Seconds spent playing the track, pauses excluded
+    pub listened: f64,
+}
+
+/// Append-only play history, one JSON object per line so a crash can at most lose a line
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open history file at {:?}", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// All entries, oldest first. Lines that fail to parse are skipped.
+    pub fn load(&self) -> Result<Vec<HistoryEntry>> {
+        let raw = match fs::read_to_string(&self.path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read history file at {:?}", self.path))
+            }
+        };
+        Ok(raw
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+/// Measures how long the current track has been playing, leaving out pauses
+#[derive(Default)]
+pub struct Recorder {
+    current: Option<HistoryEntry>,
+    listened: Duration,
+    playing_since: Option<Instant>,
+}
+
+impl Recorder {
+    /// Start timing a new track, returning the previous one if it wasn't finished
+    pub fn start(&mut self, entry: HistoryEntry, paused: bool) -> Option<HistoryEntry> {
+        self.start_at(entry, paused, Instant::now())
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.set_paused_at(paused, Instant::now());
+    }
+
+    /// The track being timed
+    pub fn current_mut(&mut self) -> Option<&mut HistoryEntry> {
+        self.current.as_mut()
+    }
+
+    /// Stop timing, returning the track with its listening time
+    pub fn finish(&mut self) -> Option<HistoryEntry> {
+        self.finish_at(Instant::now())
+    }
+
+    // The `_at` variants take the current time, so tests don't depend on real sleeps
+
+    fn start_at(
+        &mut self,
+        entry: HistoryEntry,
+        paused: bool,
+        now: Instant,
+    ) -> Option<HistoryEntry> {
+        let previous = self.finish_at(now);
+        self.current = Some(entry);
+        self.listened = Duration::ZERO;
+        self.playing_since = (!paused).then_some(now);
+        previous
+    }
+
+    fn set_paused_at(&mut self, paused: bool, now: Instant) {
+        match (paused, self.playing_since) {
+            (true, Some(since)) => {
+                self.listened += now.saturating_duration_since(since);
+                self.playing_since = None;
+            }
+            (false, None) => self.playing_since = Some(now),
+            _ => {}
+        }
+    }
+
+    fn finish_at(&mut self, now: Instant) -> Option<HistoryEntry> {
+        self.set_paused_at(true, now);
+        let mut entry = self.current.take()?;
+        entry.listened = self.listened.as_secs_f64();
+        Some(entry)
+    }
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(title: &str) -> HistoryEntry {
+        HistoryEntry {
+            video_id: Some(title.to_string()),
+            url: format!("https://www.youtube.com/watch?v={title}"),
+            title: title.to_string(),
+            channel: None,
+            played_at: 1_700_000_000,
+            listened: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_history_appends_and_skips_broken_lines() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("ytm/history.jsonl");
+        let history = History::new(&path);
+        assert!(history.load().unwrap().is_empty());
+
+        history.append(&entry("a")).unwrap();
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "{\"truncated\n").unwrap();
+        history.append(&entry("b")).unwrap();
+
+        let titles: Vec<String> = history
+            .load()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.title)
+            .collect();
+        assert_eq!(titles, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_recorder_leaves_out_pauses() {
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+        let mut recorder = Recorder::default();
+        assert!(recorder.start_at(entry("a"), true, at(0)).is_none());
+        recorder.set_paused_at(false, at(200));
+        recorder.set_paused_at(true, at(230));
+        recorder.set_paused_at(true, at(300));
+        recorder.set_paused_at(false, at(400));
+
+        let finished = recorder.start_at(entry("b"), false, at(410)).unwrap();
+        assert_eq!(finished.title, "a");
+        assert_eq!(finished.listened, 40.0);
+        let finished = recorder.finish_at(at(415)).unwrap();
+        assert_eq!((finished.title.as_str(), finished.listened), ("b", 5.0));
+        assert!(recorder.finish_at(at(500)).is_none());
+    }
+}
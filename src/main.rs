@@ -1,16 +1,28 @@
-use clap::{Parser, Subcommand};
+use std::cmp::Ordering;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 
 mod api;
 mod cache;
 mod commands;
+mod config;
+mod doctor;
+mod history;
 mod mpv;
+mod picker;
+mod queue;
+mod resume;
+mod session;
 
 #[derive(Parser)]
 #[command(name = "ytm")]
 #[command(about = "YouTube terminal music player")]
 #[command(version)]
 #[command(
-    after_help = "While in -b (background) mode, use ytm pause/resume/next/prev/stop/status commands to control background playback"
+    after_help = "While in -b (background) mode, use ytm pause/resume/toggle/next/prev/stop/status and volume/mute/seek/speed/shuffle/repeat commands to control background playback, ytm queue add/list/remove/move/clear to manage what plays next, ytm history to find what played before, and ytm fav add / ytm list to keep tracks in local lists. Arguments after -- are passed to mpv, e.g. ytm lofi -- --af=loudnorm"
 )]
 struct Cli {
     /// Search term (shortcut for `ytm search <term>`) - supports multiple words
@@ -20,45 +32,354 @@ struct Cli {
     #[arg(
         short = 'v',
         long,
+        overrides_with = "no_video",
         help = "Enable video window (default is audio-only)"
     )]
     video: bool,
 
+    /// Play audio only, even when the config file turns video on
+    #[arg(long, overrides_with = "video")]
+    no_video: bool,
+
     /// Run player in background and return to terminal (enables pause/resume/next/prev/stop/status commands)
     #[arg(
         short = 'b',
         long,
+        overrides_with = "foreground",
         help = "Run player in background (enables pause/resume/next/prev/stop/status commands)"
     )]
     background: bool,
 
+    /// Run the player in the terminal, even when the config file turns background on
+    #[arg(long, overrides_with = "background")]
+    foreground: bool,
+
+    /// Play the results in random order
+    #[arg(long)]
+    shuffle: bool,
+
+    /// Repeat the current track or the whole playlist
+    #[arg(long = "loop", value_enum, value_name = "WHAT")]
+    looping: Option<commands::LoopMode>,
+
+    /// Continue where a video was left off, even a short one (videos of 20 minutes or more always do)
+    #[arg(long, conflicts_with = "no_resume")]
+    resume: bool,
+
+    /// Start from the beginning, even for a long video that was left off part way
+    #[arg(long)]
+    no_resume: bool,
+
+    /// Stream quality preset
+    #[arg(long, env = "YTM_QUALITY", value_enum)]
+    quality: Option<config::Quality>,
+
+    /// yt-dlp format selector, overriding --quality
+    #[arg(long, value_name = "FORMAT")]
+    ytdl_format: Option<String>,
+
+    /// yt-dlp extractor-args instead of youtube:player_client=android ('' for none)
+    #[arg(long, value_name = "ARGS")]
+    extractor_args: Option<String>,
+
+    /// Name of the player session, so several players can run side by side
+    #[arg(long, global = true, env = "YTM_SESSION", value_name = "NAME")]
+    session: Option<String>,
+
+    /// Path of the mpv IPC socket (defaults to $XDG_RUNTIME_DIR/ytm/<session>.sock)
+    #[arg(long, global = true, env = "YTM_SOCKET", value_name = "PATH")]
+    socket: Option<PathBuf>,
+
+    /// Search YouTube Music for songs, albums, artists and playlists
+    #[arg(
+        short = 'm',
+        long,
+        global = true,
+        env = "YTM_MUSIC",
+        overrides_with = "music",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    music: bool,
+
+    /// Search regular YouTube, even when the config file or YTM_MUSIC turns music on
+    #[arg(long, global = true, overrides_with = "no_music")]
+    no_music: bool,
+
+    /// Only search for this kind of result
+    #[arg(long = "type", global = true, value_enum, value_name = "TYPE")]
+    item_type: Option<api::ItemKind>,
+
+    /// Only search for videos of this length
+    #[arg(long, global = true, value_enum)]
+    duration: Option<api::LengthFilter>,
+
+    /// Only search for videos uploaded within this period
+    #[arg(long, global = true, value_enum)]
+    since: Option<api::Since>,
+
+    /// Leave YouTube Shorts out of search results
+    #[arg(long, global = true)]
+    no_shorts: bool,
+
+    /// Leave livestreams out of search results
+    #[arg(long, global = true)]
+    no_live: bool,
+
+    /// After picking a playlist or album, pick the tracks to play from it
+    #[arg(long, global = true)]
+    tracks: bool,
+
+    /// Interactive selector for search results (default: fzf if installed, else builtin)
+    #[arg(long, global = true, env = "YTM_PICKER", value_enum)]
+    picker: Option<picker::Picker>,
+
+    /// Config file to read instead of $XDG_CONFIG_HOME/ytm/config.toml
+    #[arg(long, global = true, env = "YTM_CONFIG", value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Config file profile whose settings to use
+    #[arg(long, global = true, env = "YTM_PROFILE", value_name = "NAME")]
+    profile: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    #[command(hide = true)]
-    Search { query: String },
+    /// Search YouTube; pick interactively, print the results, or play one directly
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Print the results as tab-separated values (id, kind, title, channel, description, url)
+        #[arg(long, group = "mode")]
+        list: bool,
+        /// Print the results as JSON
+        #[arg(long, group = "mode")]
+        json: bool,
+        /// Play the first result without a picker
+        #[arg(long, group = "mode")]
+        first: bool,
+        /// Play the Nth result without a picker
+        #[arg(long, value_name = "N", group = "mode")]
+        pick: Option<usize>,
+    },
     #[command(hide = true)]
     Play { url: String },
+    /// Preview pane of the picker. Named so no search query can reach it.
+    #[command(name = "__preview", hide = true)]
+    Preview { file: PathBuf, key: String },
     #[command(hide = true)]
     Pause,
     #[command(hide = true)]
+    Resume,
+    #[command(hide = true)]
+    Toggle,
+    #[command(hide = true)]
+    Volume {
+        #[arg(allow_hyphen_values = true)]
+        change: Option<String>,
+    },
+    #[command(hide = true)]
+    Mute,
+    #[command(hide = true)]
+    Seek {
+        #[arg(allow_hyphen_values = true)]
+        target: String,
+    },
+    #[command(hide = true)]
+    Speed { speed: Option<f64> },
+    #[command(hide = true)]
+    Shuffle { state: Option<Switch> },
+    #[command(hide = true)]
+    Repeat { mode: Option<mpv::Repeat> },
+    #[command(hide = true)]
     Next,
     #[command(hide = true)]
     Prev,
     #[command(hide = true)]
     Stop,
     #[command(hide = true)]
-    Status,
+    Status {
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+        /// Print the status using a template, e.g. '{title} — {pos}/{dur}'
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "json")]
+        format: Option<String>,
+        /// Keep running and print a new line whenever the player state changes
+        #[arg(long)]
+        follow: bool,
+    },
+    /// Show recently played tracks, or pick some to play again
+    History {
+        /// Number of tracks to show
+        #[arg(long, default_value_t = 20, value_name = "N")]
+        limit: usize,
+        /// Print the history as JSON
+        #[arg(long)]
+        json: bool,
+        /// Pick tracks from the history and play them
+        #[arg(long, conflicts_with = "json")]
+        replay: bool,
+    },
+    /// Keep the playing track in the favourites list
+    Fav {
+        #[command(subcommand)]
+        action: FavAction,
+    },
+    /// Manage named local lists of tracks
+    List {
+        #[command(subcommand)]
+        action: ListAction,
+    },
+    /// Record what the session's player plays (started by `ytm -b`). Named so no search
+    /// query can reach it.
+    #[command(name = "__watch", hide = true)]
+    Watch,
+    /// Manage the playback queue
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+    /// Check that mpv, yt-dlp and the picker are installed and ytm's directories are usable
+    Doctor,
+    /// Show or edit the settings from the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Switch {
+    On,
+    Off,
+}
+
+#[derive(Subcommand)]
+enum FavAction {
+    /// Add the playing track to the favourites (`ytm list play favourites` plays them)
+    Add,
+}
+
+#[derive(Subcommand)]
+enum ListAction {
+    /// Create an empty list
+    Create { name: String },
+    /// Add the playing track, a URL, or picked search results to a list
+    Add { name: String, target: Vec<String> },
+    /// Remove the entry at position N from a list
+    Remove { name: String, n: usize },
+    /// Show a list's tracks, or all lists
+    Show { name: Option<String> },
+    /// Play a list, picking one when no name is given
+    Play { name: Option<String> },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective settings, after the profile, environment and flags are applied
+    Show,
+    /// Print the path of the config file
+    Path,
+    /// Open the config file in $VISUAL or $EDITOR, creating it if needed
+    Edit,
+}
+
+#[derive(Subcommand)]
+enum QueueAction {
+    /// Append a URL, or picked search results, to the queue
+    Add {
+        #[arg(required = true)]
+        target: Vec<String>,
+    },
+    /// Show the queued tracks
+    List,
+    /// Remove the entry at position N
+    Remove { n: usize },
+    /// Move the entry at position FROM to position TO
+    Move { from: usize, to: usize },
+    /// Remove all entries (the playing track is kept)
+    Clear,
+    /// Start playing the saved queue
+    Play,
+}
+
+/// Split the command line at the first `--`; what follows it is passed to mpv
+fn split_mpv_args(mut args: Vec<OsString>) -> (Vec<OsString>, Vec<String>) {
+    let Some(separator) = args.iter().position(|arg| arg == "--") else {
+        return (args, Vec::new());
+    };
+    let mpv_args = args
+        .split_off(separator)
+        .into_iter()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    (args, mpv_args)
+}
+
+/// Settings given as flags or through the environment, which take precedence over the config file
+fn given_settings(cli: &Cli, matches: &ArgMatches) -> anyhow::Result<config::Layer> {
+    // Each flag has a negating twin; whichever comes last is the one set
+    let switch = |on: bool, off: bool| match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    };
+    Ok(config::Layer {
+        video: switch(cli.video, cli.no_video),
+        background: switch(cli.background, cli.foreground),
+        music: music_setting(cli, matches)?,
+        picker: cli.picker,
+        quality: cli.quality,
+        ytdl_format: cli.ytdl_format.clone(),
+        extractor_args: cli.extractor_args.clone(),
+        session: cli.session.clone(),
+        socket: cli.socket.clone(),
+        ..Default::default()
+    })
+}
+
+/// -m/--music or --no-music, whichever was given last, else YTM_MUSIC.
+/// `--music` can't override `--no-music` in clap, as that would make YTM_MUSIC conflict with it.
+fn music_setting(cli: &Cli, matches: &ArgMatches) -> anyhow::Result<Option<bool>> {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let last = |id: &str| matches.indices_of(id).and_then(|indices| indices.max());
+    match (given("music") && cli.music, cli.no_music) {
+        (true, true) => match last("music").cmp(&last("no_music")) {
+            Ordering::Greater => Ok(Some(true)),
+            Ordering::Less => Ok(Some(false)),
+            // Indices restart in a subcommand, so flags on both sides of one can't be ordered
+            Ordering::Equal => Err(anyhow::anyhow!(
+                "--music and --no-music given on both sides of the subcommand; keep one"
+            )),
+        },
+        (true, false) => Ok(Some(true)),
+        (false, true) => Ok(Some(false)),
+        (false, false) => Ok(
+            (matches.value_source("music") == Some(ValueSource::EnvVariable)).then_some(cli.music),
+        ),
+    }
 }
 
 /// Check if a query string is a player control command
 fn is_control_command(query: &str) -> bool {
     matches!(
         query,
-        "stop" | "pause" | "resume" | "next" | "prev" | "status"
+        "stop"
+            | "pause"
+            | "resume"
+            | "toggle"
+            | "volume"
+            | "mute"
+            | "speed"
+            | "shuffle"
+            | "repeat"
+            | "next"
+            | "prev"
+            | "status"
     )
 }
 
@@ -68,19 +389,70 @@ fn execute_control_command(cmd: &str) -> anyhow::Result<()> {
         "stop" => commands::stop(),
         "pause" => commands::pause(),
         "resume" => commands::resume(),
+        "toggle" => commands::toggle(),
+        "volume" => commands::volume(None),
+        "mute" => commands::mute(),
+        "speed" => commands::speed(None),
+        "shuffle" => commands::shuffle(None),
+        "repeat" => commands::repeat(None),
         "next" => commands::next(),
         "prev" => commands::prev(),
-        "status" => commands::status(),
+        "status" => commands::status(&commands::StatusFormat::Text, false),
         _ => Err(anyhow::anyhow!("Unknown control command: {}", cmd)),
     }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    let (args, mpv_args) = split_mpv_args(std::env::args_os().collect());
+    let matches = Cli::command().get_matches_from(&args);
+    let cli = Cli::from_arg_matches(&matches)?;
+    let config_path = cli.config.clone().unwrap_or_else(config::default_path);
+    // Work without reading the config file, so a broken one can still be found and fixed
+    match &cli.command {
+        Some(Commands::Config {
+            action: ConfigAction::Path,
+        }) => return commands::config_path(&config_path),
+        Some(Commands::Config {
+            action: ConfigAction::Edit,
+        }) => return commands::config_edit(&config_path),
+        _ => {}
+    }
+    let config_file = config::ConfigFile::load(&config_path)?;
+    let profile = config_file
+        .profile(cli.profile.as_deref())
+        .map(str::to_string);
+    let settings = config::Settings::resolve(
+        given_settings(&cli, &matches)?.or(config_file.layer(cli.profile.as_deref())?),
+    )?;
+    session::init(settings.session.clone(), settings.socket.clone())?;
+    config::init(settings)?;
+    let settings = config::current();
 
-    // By default, audio-only. -v enables video.
-    let no_video = !cli.video;
+    let options = commands::PlayOptions {
+        // By default, audio-only. -v enables video.
+        no_video: !settings.video,
+        background: settings.background,
+        shuffle: cli.shuffle,
+        looping: cli.looping,
+        resume: if cli.resume {
+            resume::ResumePolicy::Always
+        } else if cli.no_resume {
+            resume::ResumePolicy::Never
+        } else {
+            resume::ResumePolicy::Auto
+        },
+        mpv_args,
+    };
+    let picker = settings.picker;
+    let filters = api::SearchFilters {
+        item_type: cli.item_type,
+        length: cli.duration,
+        since: cli.since,
+        no_shorts: cli.no_shorts,
+        no_live: cli.no_live,
+        music: settings.music,
+    };
 
     if !cli.query.is_empty() {
         let query = cli.query.join(" ");
@@ -97,19 +469,102 @@ async fn main() -> anyhow::Result<()> {
                 );
             }
         }
-        return commands::search_and_play(&query, no_video, cli.background).await;
+        return commands::search_and_play(
+            &query,
+            &filters,
+            &commands::SearchMode::Interactive(picker),
+            cli.tracks,
+            &options,
+        )
+        .await;
     }
 
     match cli.command {
-        Some(Commands::Search { query }) => {
-            commands::search_and_play(&query, no_video, cli.background).await
+        Some(Commands::Search {
+            query,
+            list,
+            json,
+            first,
+            pick,
+        }) => {
+            let mode = if list {
+                commands::SearchMode::List
+            } else if json {
+                commands::SearchMode::Json
+            } else if first {
+                commands::SearchMode::Pick(1)
+            } else if let Some(n) = pick {
+                commands::SearchMode::Pick(n)
+            } else {
+                commands::SearchMode::Interactive(picker)
+            };
+            commands::search_and_play(&query.join(" "), &filters, &mode, cli.tracks, &options).await
         }
-        Some(Commands::Play { url }) => commands::play(&url, no_video, cli.background),
+        Some(Commands::Play { url }) => commands::play(&url, &options),
+        Some(Commands::Preview { file, key }) => commands::preview(&file, &key),
         Some(Commands::Pause) => commands::pause(),
+        Some(Commands::Resume) => commands::resume(),
+        Some(Commands::Toggle) => commands::toggle(),
+        Some(Commands::Volume { change }) => commands::volume(change.as_deref()),
+        Some(Commands::Mute) => commands::mute(),
+        Some(Commands::Seek { target }) => commands::seek(&target),
+        Some(Commands::Speed { speed }) => commands::speed(speed),
+        Some(Commands::Shuffle { state }) => {
+            commands::shuffle(state.map(|state| matches!(state, Switch::On)))
+        }
+        Some(Commands::Repeat { mode }) => commands::repeat(mode),
         Some(Commands::Next) => commands::next(),
         Some(Commands::Prev) => commands::prev(),
         Some(Commands::Stop) => commands::stop(),
-        Some(Commands::Status) => commands::status(),
+        Some(Commands::Status {
+            json,
+            format,
+            follow,
+        }) => {
+            let format = match (json, format) {
+                (true, _) => commands::StatusFormat::Json,
+                (false, Some(template)) => commands::StatusFormat::Template(template),
+                (false, None) => commands::StatusFormat::Text,
+            };
+            commands::status(&format, follow)
+        }
+        Some(Commands::History {
+            limit,
+            json,
+            replay,
+        }) => commands::history(limit, json, replay.then_some(picker), &options),
+        Some(Commands::Fav { action }) => match action {
+            FavAction::Add => commands::fav_add(),
+        },
+        Some(Commands::List { action }) => match action {
+            ListAction::Create { name } => commands::list_create(&name),
+            ListAction::Add { name, target } => {
+                let target = (!target.is_empty()).then(|| target.join(" "));
+                commands::list_add(&name, target.as_deref(), &filters, picker, cli.tracks).await
+            }
+            ListAction::Remove { name, n } => commands::list_remove(&name, n),
+            ListAction::Show { name } => commands::list_show(name.as_deref()),
+            ListAction::Play { name } => {
+                commands::list_play(name.as_deref(), picker, cli.tracks, &options)
+            }
+        },
+        Some(Commands::Watch) => commands::watch(),
+        Some(Commands::Queue { action }) => match action {
+            QueueAction::Add { target } => {
+                commands::queue_add(&target.join(" "), &filters, picker, cli.tracks).await
+            }
+            QueueAction::List => commands::queue_list(),
+            QueueAction::Remove { n } => commands::queue_remove(n),
+            QueueAction::Move { from, to } => commands::queue_move(from, to),
+            QueueAction::Clear => commands::queue_clear(),
+            QueueAction::Play => commands::queue_play(&options),
+        },
+        Some(Commands::Doctor) => commands::doctor(picker),
+        Some(Commands::Config { action }) => match action {
+            ConfigAction::Show => commands::config_show(&config_path, profile.as_deref()),
+            ConfigAction::Path => commands::config_path(&config_path),
+            ConfigAction::Edit => commands::config_edit(&config_path),
+        },
         None => {
             eprintln!("Usage: ytm <query> or ytm search <query>");
             Ok(())
@@ -1,143 +1,613 @@
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
-
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use serde_json::{json, Value};
 
-/// Get the path for MPV Unix socket
-fn mpv_socket() -> PathBuf {
-    PathBuf::from("/tmp/ytm-mpv.sock")
+use crate::api::video_id_from_url;
+use crate::doctor;
+use crate::session;
+
+/// Get the path for MPV Unix socket of the current session
+pub fn mpv_socket() -> &'static Path {
+    session::current().socket()
+}
+
+/// Socket of a foreground player. It is private to this ytm process, so control
+/// commands keep addressing the session's background player.
+pub fn foreground_socket() -> PathBuf {
+    let socket = mpv_socket();
+    let stem = socket.file_stem().unwrap_or_default().to_string_lossy();
+    socket.with_file_name(format!("{}-{}.sock", stem, std::process::id()))
 }
 
-/// Get the path for MPV PID file
+/// Get the path for MPV PID file, next to the socket
 fn mpv_pid_file() -> PathBuf {
-    PathBuf::from("/tmp/ytm-mpv.pid")
+    mpv_socket().with_extension("pid")
+}
+
+/// mpv.conf profile applied to every player ytm starts, if the user defined it
+pub const PROFILE: &str = "ytm";
+
+/// Directory mpv reads mpv.conf from
+fn config_dir() -> Option<PathBuf> {
+    if let Some(home) = std::env::var_os("MPV_HOME") {
+        return Some(PathBuf::from(home));
+    }
+    // mpv uses ~/.config on every Unix, unlike dirs::config_dir on macOS
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))?;
+    Some(config.join("mpv"))
+}
+
+/// Whether the user's mpv.conf has a `[name]` profile section. mpv refuses to start
+/// with an unknown `--profile`.
+pub fn has_profile(name: &str) -> bool {
+    config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join("mpv.conf")).ok())
+        .is_some_and(|conf| defines_profile(&conf, name))
+}
+
+fn defines_profile(conf: &str, name: &str) -> bool {
+    let section = format!("[{name}]");
+    conf.lines().any(|line| line.trim() == section)
 }
 
+/// Properties fetched in one batch by [`Mpv::get_status`]
+const STATUS_PROPERTIES: &[&str] = &[
+    "media-title",
+    "time-pos",
+    "duration",
+    "playlist-pos-1",
+    "playlist-count",
+    // Album/playlist title from YouTube metadata
+    "metadata/album",
+    "metadata/ytdl_playlist_title",
+    "volume",
+    "mute",
+    "speed",
+    "pause",
+    "path",
+    "metadata/by-key/uploader",
+    "metadata/by-key/artist",
+    "shuffle",
+    "loop-file",
+    "loop-playlist",
+];
+
+/// How long to wait for mpv to answer a command before treating the socket as wedged
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error reported by mpv in the `error` field of a command reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MpvError {
+    /// mpv answered the command with something other than "success"
+    Command { command: String, error: String },
+    /// The IPC socket was closed before a reply arrived
+    Disconnected,
+}
+
+impl std::fmt::Display for MpvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MpvError::Command { command, error } => {
+                write!(f, "mpv rejected command {}: {}", command, error)
+            }
+            MpvError::Disconnected => write!(f, "mpv closed the IPC connection"),
+        }
+    }
+}
+
+impl std::error::Error for MpvError {}
+
 pub struct Mpv {
     stream: UnixStream,
     reader: BufReader<UnixStream>,
+    /// Partially read reply line, kept across read timeouts
+    line: String,
+    next_request_id: u64,
+    /// Events read while waiting for a reply, oldest first
+    events: VecDeque<Value>,
 }
 
 impl Mpv {
     /// Connect to MPV IPC socket
     pub fn connect() -> Result<Self> {
-        let stream = UnixStream::connect(mpv_socket())?;
+        Self::connect_at(mpv_socket()).map_err(|err| {
+            if is_not_listening(&err) {
+                err.context(format!(
+                    "No player running in session '{}'; start one with `ytm -b <query>`, or {}",
+                    session::current().name(),
+                    doctor::HINT
+                ))
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Connect to a player listening on a socket other than the session's
+    pub fn connect_at(socket: &Path) -> Result<Self> {
+        Self::from_stream(UnixStream::connect(socket)?)
+    }
+
+    /// Connect to MPV IPC socket, retrying while a freshly spawned player creates it
+    pub fn connect_timeout(timeout: Duration) -> Result<Self> {
+        Self::connect_at_timeout(mpv_socket(), timeout)
+    }
+
+    /// Like [`Mpv::connect_timeout`], for a socket other than the session's
+    pub fn connect_at_timeout(socket: &Path, timeout: Duration) -> Result<Self> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match Self::connect_at(socket) {
+                Ok(client) => return Ok(client),
+                Err(err) if Instant::now() >= deadline => return Err(err),
+                Err(_) => std::thread::sleep(Duration::from_millis(50)),
+            }
+        }
+    }
+
+    fn from_stream(stream: UnixStream) -> Result<Self> {
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
         let reader = BufReader::new(stream.try_clone()?);
-        Ok(Self { stream, reader })
+        Ok(Self {
+            stream,
+            reader,
+            line: String::new(),
+            next_request_id: 1,
+            events: VecDeque::new(),
+        })
     }
 
-    /// Send a command to MPV
-    pub fn send_command(&mut self, cmd: Value) -> Result<()> {
+    /// Tag a command with a fresh request id and serialise it as one IPC line
+    fn encode(&mut self, mut cmd: Value) -> Result<(u64, String)> {
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        cmd["request_id"] = json!(request_id);
+
         let line = serde_json::to_string(&cmd)? + "\n";
+        Ok((request_id, line))
+    }
+
+    /// Send a command to MPV tagged with a fresh request id, without waiting for the reply
+    pub fn send_command(&mut self, cmd: Value) -> Result<u64> {
+        let (request_id, line) = self.encode(cmd)?;
         self.stream.write_all(line.as_bytes())?;
+        Ok(request_id)
+    }
+
+    /// Run a command and return the `data` of its reply
+    pub fn command(&mut self, args: Value) -> Result<Option<Value>> {
+        let request_id = self.send_command(json!({ "command": args.clone() }))?;
+        let reply = self.wait_reply(request_id)?;
+        reply_data(&args, &reply)
+    }
+
+    /// Read messages until the reply for `request_id` arrives, buffering events on the way
+    fn wait_reply(&mut self, request_id: u64) -> Result<Value> {
+        loop {
+            let message = self.read_message()?;
+            if message.get("event").is_some() {
+                self.events.push_back(message);
+            } else if message.get("request_id").and_then(Value::as_u64) == Some(request_id) {
+                return Ok(message);
+            }
+            // Anything else is a stale reply to a request nobody waits for anymore
+        }
+    }
+
+    /// Read the next JSON message from the socket
+    fn read_message(&mut self) -> Result<Value> {
+        loop {
+            // A read timeout can interrupt a line halfway, so the partial line is kept in `self.line`
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Err(MpvError::Disconnected.into());
+            }
+            let line = std::mem::take(&mut self.line);
+            if let Ok(message) = serde_json::from_str::<Value>(&line) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Ask mpv to send a `property-change` event, tagged with `id`, whenever `property` changes
+    pub fn observe_property(&mut self, id: u64, property: &str) -> Result<()> {
+        self.command(json!(["observe_property", id, property]))?;
         Ok(())
     }
 
-    /// Get a property value from MPV
-    #[allow(dead_code)] // Part of public API, may be used in future
-    pub fn get_property(&mut self, property: &str) -> Result<Option<Value>> {
-        self.send_command(json!({"command": ["get_property", property]}))?;
+    /// Next event sent by mpv (e.g. `start-file`, `property-change`), blocking until one arrives
+    pub fn next_event(&mut self) -> Result<Value> {
+        if let Some(event) = self.events.pop_front() {
+            return Ok(event);
+        }
+
+        self.stream.set_read_timeout(None)?;
+        let event = loop {
+            match self.read_message() {
+                Ok(message) if message.get("event").is_some() => break Ok(message),
+                Ok(_) => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        event
+    }
+
+    /// Wait for one of the named events, discarding others, or `None` once `timeout` elapses
+    pub fn wait_for_event(&mut self, names: &[&str], timeout: Duration) -> Result<Option<Value>> {
+        let is_wanted = |message: &Value| {
+            message
+                .get("event")
+                .and_then(Value::as_str)
+                .is_some_and(|name| names.contains(&name))
+        };
+
+        while let Some(event) = self.events.pop_front() {
+            if is_wanted(&event) {
+                return Ok(Some(event));
+            }
+        }
 
-        let mut buf = String::new();
-        if self.reader.read_line(&mut buf).is_ok() {
-            if let Ok(val) = serde_json::from_str::<Value>(&buf) {
-                return Ok(val.get("data").cloned());
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+
+            self.stream.set_read_timeout(Some(remaining))?;
+            let message = self.read_message();
+            self.stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+
+            match message {
+                Ok(message) if is_wanted(&message) => return Ok(Some(message)),
+                Ok(_) => {}
+                Err(err) if is_timeout(&err) => return Ok(None),
+                Err(err) => return Err(err),
             }
         }
-        Ok(None)
     }
 
-    /// Get multiple properties at once  
-    pub fn get_status(&mut self) -> Result<MpvStatus> {
-        // Get properties individually for reliable parsing
-        let title = self
-            .get_property("media-title")?
-            .and_then(|v| v.as_str().map(|s| s.to_string()));
+    /// Block until mpv has a file playing, giving up silently after `timeout`
+    pub fn wait_until_playing(&mut self, timeout: Duration) -> Result<()> {
+        if self.get_property("time-pos")?.is_some() {
+            return Ok(());
+        }
+        self.wait_for_event(&["file-loaded", "playback-restart"], timeout)?;
+        Ok(())
+    }
 
-        let position = self.get_property("time-pos")?.and_then(|v| v.as_f64());
+    /// Get a property value from MPV, or `None` if it is currently unavailable
+    pub fn get_property(&mut self, property: &str) -> Result<Option<Value>> {
+        let request_id = self.send_command(json!({ "command": ["get_property", property] }))?;
+        let reply = self.wait_reply(request_id)?;
+        property_data(property, &reply)
+    }
 
-        let duration = self.get_property("duration")?.and_then(|v| v.as_f64());
+    /// Get several properties in a single round-trip, in the order requested
+    pub fn get_properties(&mut self, properties: &[&str]) -> Result<Vec<Option<Value>>> {
+        let mut batch = String::new();
+        let mut request_ids = Vec::with_capacity(properties.len());
+        for property in properties {
+            let (request_id, line) =
+                self.encode(json!({ "command": ["get_property", property] }))?;
+            batch.push_str(&line);
+            request_ids.push(request_id);
+        }
+        self.stream.write_all(batch.as_bytes())?;
 
-        // Get playlist info
-        let playlist_pos = self
-            .get_property("playlist-pos-1")?
-            .and_then(|v| v.as_i64());
+        let mut replies = HashMap::new();
+        while replies.len() < request_ids.len() {
+            let message = self.read_message()?;
+            if message.get("event").is_some() {
+                self.events.push_back(message);
+            } else if let Some(id) = message.get("request_id").and_then(Value::as_u64) {
+                if request_ids.contains(&id) {
+                    replies.insert(id, message);
+                }
+            }
+        }
 
-        let playlist_count = self
-            .get_property("playlist-count")?
-            .and_then(|v| v.as_i64());
+        properties
+            .iter()
+            .zip(&request_ids)
+            .map(|(property, id)| property_data(property, &replies[id]))
+            .collect()
+    }
 
-        // Get album/playlist title from YouTube metadata
-        let album = self
-            .get_property("metadata/album")?
-            .and_then(|v| v.as_str().map(|s| s.to_string()));
+    /// Get multiple properties at once
+    pub fn get_status(&mut self) -> Result<MpvStatus> {
+        let values: HashMap<&str, Value> = STATUS_PROPERTIES
+            .iter()
+            .copied()
+            .zip(self.get_properties(STATUS_PROPERTIES)?)
+            .filter_map(|(property, value)| Some((property, value?)))
+            .collect();
 
-        // Get YouTube playlist title
-        let playlist_title = self
-            .get_property("metadata/ytdl_playlist_title")?
-            .and_then(|v| v.as_str().map(|s| s.to_string()));
+        let string = |property| {
+            values
+                .get(property)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let float = |property| values.get(property).and_then(Value::as_f64);
+        let int = |property| values.get(property).and_then(Value::as_i64);
+        let boolean = |property| values.get(property).and_then(Value::as_bool);
 
+        let url = string("path");
         Ok(MpvStatus {
-            title,
-            position,
-            duration,
-            playlist_pos,
-            playlist_count,
-            album,
-            playlist_title,
+            title: string("media-title"),
+            channel: string("metadata/by-key/uploader")
+                .or_else(|| string("metadata/by-key/artist")),
+            video_id: url.as_deref().and_then(video_id_from_url),
+            url,
+            paused: boolean("pause").unwrap_or(false),
+            position: float("time-pos"),
+            duration: float("duration"),
+            playlist_pos: int("playlist-pos-1"),
+            playlist_count: int("playlist-count"),
+            album: string("metadata/album"),
+            playlist_title: string("metadata/ytdl_playlist_title"),
+            volume: float("volume"),
+            muted: boolean("mute").unwrap_or(false),
+            speed: float("speed"),
+            shuffle: boolean("shuffle").unwrap_or(false),
+            repeat: if is_looping(values.get("loop-file")) {
+                Repeat::One
+            } else if is_looping(values.get("loop-playlist")) {
+                Repeat::All
+            } else {
+                Repeat::Off
+            },
         })
     }
 
+    /// Change volume by `delta` percent
+    pub fn add_volume(&mut self, delta: f64) -> Result<()> {
+        self.command(json!(["add", "volume", delta]))?;
+        Ok(())
+    }
+
+    /// Toggle mute
+    pub fn toggle_mute(&mut self) -> Result<()> {
+        self.command(json!(["cycle", "mute"]))?;
+        Ok(())
+    }
+
+    /// Seek using one of mpv's seek flags (`relative`, `absolute`, `absolute-percent`, ...)
+    pub fn seek(&mut self, amount: f64, flag: &str) -> Result<()> {
+        self.command(json!(["seek", amount, flag]))?;
+        Ok(())
+    }
+
+    /// Set a property value
+    pub fn set_property(&mut self, property: &str, value: Value) -> Result<()> {
+        self.command(json!(["set_property", property, value]))?;
+        Ok(())
+    }
+
+    /// Pause or resume playback
+    pub fn set_pause(&mut self, pause: bool) -> Result<()> {
+        self.set_property("pause", json!(pause))
+    }
+
     /// Toggle pause state
-    #[allow(dead_code)] // Part of public API
     pub fn toggle_pause(&mut self) -> Result<()> {
-        self.send_command(json!({"command": ["cycle", "pause"]}))
+        self.command(json!(["cycle", "pause"]))?;
+        Ok(())
+    }
+
+    /// Whether playback is currently paused
+    pub fn is_paused(&mut self) -> Result<bool> {
+        Ok(self
+            .get_property("pause")?
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false))
     }
 
     /// Go to next track
     #[allow(dead_code)] // Part of public API
     pub fn next(&mut self) -> Result<()> {
-        self.send_command(json!({"command": ["playlist-next", "force"]}))
+        self.command(json!(["playlist-next", "force"]))?;
+        Ok(())
     }
 
     /// Go to previous track
     #[allow(dead_code)] // Part of public API
     pub fn prev(&mut self) -> Result<()> {
-        self.send_command(json!({"command": ["playlist-prev", "force"]}))
+        self.command(json!(["playlist-prev", "force"]))?;
+        Ok(())
     }
 
     /// Stop playback
     #[allow(dead_code)] // Part of public API
     pub fn stop(&mut self) -> Result<()> {
-        self.send_command(json!({"command": ["stop"]}))
+        self.command(json!(["stop"]))?;
+        Ok(())
     }
+
+    /// Shuffle the playlist, or restore its order. The `shuffle` option records the state.
+    pub fn set_shuffle(&mut self, on: bool) -> Result<()> {
+        self.set_property("shuffle", json!(on))?;
+        let command = if on {
+            "playlist-shuffle"
+        } else {
+            "playlist-unshuffle"
+        };
+        self.command(json!([command]))?;
+        Ok(())
+    }
+
+    pub fn set_repeat(&mut self, repeat: Repeat) -> Result<()> {
+        let (file, playlist) = match repeat {
+            Repeat::Off => ("no", "no"),
+            Repeat::One => ("inf", "no"),
+            Repeat::All => ("no", "inf"),
+        };
+        self.set_property("loop-file", json!(file))?;
+        self.set_property("loop-playlist", json!(playlist))
+    }
+
+    /// URLs of the playlist entries, in playlist order
+    pub fn playlist_urls(&mut self) -> Result<Vec<String>> {
+        let playlist = self.get_property("playlist")?.unwrap_or_default();
+        Ok(playlist
+            .as_array()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| entry.get("filename")?.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Append a URL to the playlist, starting playback if mpv is idle.
+    /// `title` replaces the media title yt-dlp reports for it.
+    pub fn append(&mut self, url: &str, title: Option<&str>) -> Result<()> {
+        match title {
+            // Named arguments, since the position of `options` differs between mpv versions.
+            // `%N%` quotes the value so commas in the title don't split the option list.
+            Some(title) => self.command(json!({
+                "name": "loadfile",
+                "url": url,
+                "flags": "append-play",
+                "options": format!("force-media-title=%{}%{}", title.len(), title),
+            }))?,
+            None => self.command(json!(["loadfile", url, "append-play"]))?,
+        };
+        Ok(())
+    }
+
+    /// Remove the playlist entry at 0-based `index`
+    pub fn playlist_remove(&mut self, index: usize) -> Result<()> {
+        self.command(json!(["playlist-remove", index]))?;
+        Ok(())
+    }
+
+    /// Move the playlist entry at 0-based `from` so it ends up at 0-based `to`
+    pub fn playlist_move(&mut self, from: usize, to: usize) -> Result<()> {
+        // mpv inserts before the entry currently at the target index
+        let target = if from < to { to + 1 } else { to };
+        self.command(json!(["playlist-move", from, target]))?;
+        Ok(())
+    }
+
+    /// Remove every playlist entry except the one currently playing
+    pub fn playlist_clear(&mut self) -> Result<()> {
+        self.command(json!(["playlist-clear"]))?;
+        Ok(())
+    }
+}
+
+/// Repeat mode, kept in mpv's `loop-file` and `loop-playlist` options
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Repeat {
+    #[default]
+    Off,
+    /// Repeat the current track
+    One,
+    /// Repeat the whole playlist
+    All,
 }
 
+impl Repeat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Repeat::Off => "off",
+            Repeat::One => "one",
+            Repeat::All => "all",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct MpvStatus {
     pub title: Option<String>,
+    pub channel: Option<String>,
+    pub url: Option<String>,
+    pub video_id: Option<String>,
+    pub paused: bool,
     pub position: Option<f64>,
     pub duration: Option<f64>,
     pub playlist_pos: Option<i64>,
     pub playlist_count: Option<i64>,
     pub album: Option<String>,
     pub playlist_title: Option<String>,
+    pub volume: Option<f64>,
+    pub muted: bool,
+    pub speed: Option<f64>,
+    pub shuffle: bool,
+    pub repeat: Repeat,
 }
 
-/// Send a one-off command to MPV (convenience function)
-pub fn send_mpv_command(cmd: Value) -> Result<()> {
-    let mut stream = UnixStream::connect(mpv_socket())?;
-    let line = serde_json::to_string(&cmd)? + "\n";
-    stream.write_all(line.as_bytes())?;
+/// Whether a `loop-file`/`loop-playlist` value repeats: `"inf"` or a count, not `"no"`/`false`
+fn is_looping(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::Bool(on)) => *on,
+        Some(Value::String(text)) => text != "no",
+        Some(Value::Number(count)) => count.as_u64() != Some(0),
+        _ => false,
+    }
+}
+
+/// Extract the `data` of a reply, turning mpv's `error` field into an [`MpvError`]
+fn reply_data(args: &Value, reply: &Value) -> Result<Option<Value>> {
+    match reply.get("error").and_then(Value::as_str) {
+        Some("success") | None => Ok(reply.get("data").cloned()),
+        Some(error) => Err(MpvError::Command {
+            command: args.to_string(),
+            error: error.to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Like [`reply_data`] for `get_property`, but an unavailable property is just `None`
+fn property_data(property: &str, reply: &Value) -> Result<Option<Value>> {
+    if reply.get("error").and_then(Value::as_str) == Some("property unavailable") {
+        return Ok(None);
+    }
+    reply_data(&json!(["get_property", property]), reply)
+}
 
-    // Read response to avoid broken pipe errors
-    let mut reader = BufReader::new(stream.try_clone()?);
-    let mut response = String::new();
-    let _ = reader.read_line(&mut response); // Ignore response, just consume it
+/// Whether an error means mpv went away, e.g. because it quit
+pub fn is_disconnected(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref(), Some(MpvError::Disconnected))
+}
 
+/// Whether connecting failed because nothing listens on the socket
+fn is_not_listening(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+        )
+    })
+}
+
+/// Whether an error is a socket read timing out
+fn is_timeout(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some_and(|err| {
+        matches!(
+            err.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+        )
+    })
+}
+
+/// Send a one-off command to MPV (convenience function)
+pub fn send_mpv_command(args: Value) -> Result<()> {
+    Mpv::connect()?.command(args)?;
     Ok(())
 }
 
@@ -146,14 +616,259 @@ pub fn is_running() -> bool {
     UnixStream::connect(mpv_socket()).is_ok()
 }
 
-/// Kill MPV process using PID file (fallback method)
+/// Contents of the PID file written for a background player
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct PidRecord {
+    pid: i32,
+    /// Process start time, from /proc or `ps`, to tell our mpv apart from a reused PID
+    started: String,
+}
+
+/// Record the PID of a freshly spawned background player
+pub fn write_pid_file(pid: u32) -> Result<()> {
+    let pid = i32::try_from(pid)?;
+    let started = process_start_time(pid)
+        .ok_or_else(|| anyhow::anyhow!("mpv (pid {}) exited right after starting", pid))?;
+    let raw = serde_json::to_vec(&PidRecord { pid, started })?;
+    fs::write(mpv_pid_file(), raw)?;
+    Ok(())
+}
+
+fn read_pid_file() -> Option<PidRecord> {
+    let raw = fs::read(mpv_pid_file()).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+/// Ask `ps` about a single column of process `pid`
+#[cfg(not(target_os = "linux"))]
+fn ps_field(pid: i32, field: &str) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", &format!("{field}="), "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !value.is_empty()).then_some(value)
+}
+
+#[cfg(target_os = "linux")]
+fn process_name(pid: i32) -> Option<String> {
+    let comm = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    Some(comm.trim_end().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_name(pid: i32) -> Option<String> {
+    ps_field(pid, "comm")
+}
+
+/// Read from /proc where there is one, so a missing `ps` can't lose track of the player
+#[cfg(target_os = "linux")]
+fn process_start_time(pid: i32) -> Option<String> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    start_time_from_stat(&stat)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_start_time(pid: i32) -> Option<String> {
+    ps_field(pid, "lstart")
+}
+
+/// Field 22 of `/proc/<pid>/stat`, the start time in clock ticks since boot. Fields are
+/// counted from the end of the command name, which may itself contain spaces and parens.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn start_time_from_stat(stat: &str) -> Option<String> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19).map(str::to_string)
+}
+
+/// Whether the recorded process is still alive, is named `name` and is not a reused PID
+fn is_recorded_process(record: &PidRecord, name: &str) -> bool {
+    let command_matches = process_name(record.pid).is_some_and(|comm| {
+        Path::new(&comm)
+            .file_name()
+            .is_some_and(|file| file.to_string_lossy().starts_with(name))
+    });
+    command_matches && process_start_time(record.pid).as_deref() == Some(record.started.as_str())
+}
+
+/// Kill MPV process using PID file (fallback method).
+/// Sends SIGTERM, escalates to SIGKILL after a grace period, then removes the stale socket and PID file.
 pub fn force_kill() -> Result<()> {
-    if let Ok(pid_str) = fs::read_to_string(mpv_pid_file()) {
-        if let Ok(pid) = pid_str.trim().parse::<i32>() {
-            use nix::sys::signal::{kill, Signal};
-            use nix::unistd::Pid;
-            let _ = kill(Pid::from_raw(pid), Signal::SIGTERM);
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    if let Some(record) = read_pid_file().filter(|record| is_recorded_process(record, "mpv")) {
+        let pid = Pid::from_raw(record.pid);
+        let _ = kill(pid, Signal::SIGTERM);
+
+        let deadline = Instant::now() + Duration::from_secs(3);
+        while kill(pid, None).is_ok() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        if kill(pid, None).is_ok() {
+            let _ = kill(pid, Signal::SIGKILL);
         }
     }
+
+    let _ = fs::remove_file(mpv_socket());
+    let _ = fs::remove_file(mpv_pid_file());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_defines_profile() {
+        let conf = "volume=80\n\n[ytm]\naf=loudnorm\n[ytm-video]\n";
+        assert!(defines_profile(conf, "ytm"));
+        assert!(defines_profile(conf, "ytm-video"));
+        assert!(!defines_profile("# [ytm]\nytm=1", "ytm"));
+    }
+
+    /// Answer each request on `server` with the lines produced by `respond`
+    fn fake_mpv<F>(server: UnixStream, requests: usize, respond: F) -> thread::JoinHandle<()>
+    where
+        F: Fn(&Value) -> Vec<Value> + Send + 'static,
+    {
+        thread::spawn(move || {
+            let mut writer = server.try_clone().unwrap();
+            let mut reader = BufReader::new(server);
+            for _ in 0..requests {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                for reply in respond(&request) {
+                    writer
+                        .write_all((reply.to_string() + "\n").as_bytes())
+                        .unwrap();
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn routes_replies_by_request_id_and_buffers_events() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = fake_mpv(server, 1, |request| {
+            let id = request["request_id"].as_u64().unwrap();
+            vec![
+                json!({"event": "start-file", "playlist_entry_id": 2}),
+                json!({"request_id": id + 100, "error": "success", "data": "stale"}),
+                json!({"request_id": id, "error": "success", "data": "Fantastic Track"}),
+            ]
+        });
+
+        let mut mpv = Mpv::from_stream(client).unwrap();
+        let title = mpv.get_property("media-title").unwrap();
+        assert_eq!(title, Some(json!("Fantastic Track")));
+
+        let event = mpv.next_event().unwrap();
+        assert_eq!(event["event"], "start-file");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn surfaces_mpv_errors() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = fake_mpv(server, 2, |request| {
+            let id = request["request_id"].as_u64().unwrap();
+            let error = if request["command"][1] == "time-pos" {
+                "property unavailable"
+            } else {
+                "invalid parameter"
+            };
+            vec![json!({"request_id": id, "error": error})]
+        });
+
+        let mut mpv = Mpv::from_stream(client).unwrap();
+        assert_eq!(mpv.get_property("time-pos").unwrap(), None);
+
+        let err = mpv.command(json!(["seek", "nonsense"])).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MpvError>(),
+            Some(MpvError::Command { error, .. }) if error == "invalid parameter"
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn batches_property_requests_in_one_round_trip() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let mut writer = server.try_clone().unwrap();
+            let mut reader = BufReader::new(server);
+            let requests: Vec<Value> = (0..3)
+                .map(|_| {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    serde_json::from_str(&line).unwrap()
+                })
+                .collect();
+
+            // Reply out of order with an event in between
+            let id = |i: usize| requests[i]["request_id"].as_u64().unwrap();
+            let replies = [
+                json!({"request_id": id(2), "error": "success", "data": 3}),
+                json!({"event": "playback-restart"}),
+                json!({"request_id": id(0), "error": "success", "data": "Chill Mix"}),
+                json!({"request_id": id(1), "error": "property unavailable"}),
+            ];
+            for reply in replies {
+                writer
+                    .write_all((reply.to_string() + "\n").as_bytes())
+                    .unwrap();
+            }
+            // Keep the connection open until the client is done with it
+            writer
+        });
+
+        let mut mpv = Mpv::from_stream(client).unwrap();
+        let values = mpv
+            .get_properties(&["media-title", "duration", "playlist-count"])
+            .unwrap();
+        assert_eq!(values, [Some(json!("Chill Mix")), None, Some(json!(3))]);
+
+        let event = mpv
+            .wait_for_event(&["playback-restart"], Duration::from_millis(100))
+            .unwrap();
+        assert!(event.is_some());
+        let timed_out = mpv
+            .wait_for_event(&["file-loaded"], Duration::from_millis(50))
+            .unwrap();
+        assert!(timed_out.is_none());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn validates_recorded_process() {
+        let pid = std::process::id() as i32;
+        let started = process_start_time(pid).unwrap();
+        let comm = process_name(pid).unwrap();
+        let name = Path::new(&comm)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let record = PidRecord { pid, started };
+        assert!(is_recorded_process(&record, &name));
+        assert!(!is_recorded_process(&record, "mpv"));
+
+        let reused = PidRecord {
+            pid,
+            started: "Thu Jan  1 00:00:00 1970".to_string(),
+        };
+        assert!(!is_recorded_process(&reused, &name));
+    }
+
+    #[test]
+    fn reads_start_time_past_odd_process_names() {
+        let stat =
+            "4242 (mpv (ytm) x) S 1 4242 4242 0 -1 4194560 1 0 0 0 0 0 0 0 20 0 1 0 987654 0";
+        assert_eq!(start_time_from_stat(stat).as_deref(), Some("987654"));
+        assert!(start_time_from_stat("4242 (mpv) S 1").is_none());
+    }
+}
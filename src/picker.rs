@@ -0,0 +1,312 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use serde::{Deserialize, Serialize};
+
+/// One selectable line: a stable `key` identifying it and the `label` shown to the user
+pub struct PickerEntry {
+    pub key: String,
+    pub label: String,
+}
+
+#[derive(Default)]
+pub struct PickOptions<'a> {
+    /// Allow choosing several entries (Tab in fzf and the built-in picker)
+    pub multi: bool,
+    /// Shell command previewing the highlighted entry; `{}` is replaced by its key
+    pub preview: Option<&'a str>,
+}
+
+/// Interactive selector used to choose a search result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Picker {
+    Fzf,
+    Skim,
+    /// Selector built into ytm, for systems without fzf
+    Builtin,
+    /// No picker: take the first entry
+    None,
+}
+
+impl Picker {
+    /// fzf when it is installed, the built-in selector otherwise
+    pub fn detect() -> Self {
+        if find_in_path("fzf").is_some() {
+            Picker::Fzf
+        } else {
+            Picker::Builtin
+        }
+    }
+
+    /// Let the user choose among `entries`, returning the chosen indices in selection order
+    /// (empty if cancelled)
+    pub fn pick(self, entries: &[PickerEntry], options: &PickOptions) -> Result<Vec<usize>> {
+        match self {
+            Picker::Fzf => pick_external("fzf", entries, options),
+            Picker::Skim => pick_external("sk", entries, options),
+            Picker::Builtin => {
+                let labels: Vec<String> = entries.iter().map(|e| e.label.clone()).collect();
+                pick_builtin(&labels, options.multi)
+            }
+            Picker::None => Ok(if entries.is_empty() { vec![] } else { vec![0] }),
+        }
+    }
+}
+
+/// Locate an executable on PATH
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Run an fzf-compatible picker. Each line carries the entry index and key in hidden
+/// tab-separated columns, so the selection never depends on labels being unique.
+fn pick_external(
+    program: &str,
+    entries: &[PickerEntry],
+    options: &PickOptions,
+) -> Result<Vec<usize>> {
+    if find_in_path(program).is_none() {
+        return Err(anyhow::anyhow!(
+            "{} not found on PATH; install it or use --picker builtin",
+            program
+        ));
+    }
+
+    let mut command = Command::new(program);
+    command.args(["--delimiter", "\t", "--with-nth", "3.."]);
+    if options.multi {
+        command.arg("--multi");
+    }
+    if let Some(preview) = options.preview {
+        command.args(["--preview", &preview.replace("{}", "{2}")]);
+        command.args(["--preview-window", "right:40%:wrap"]);
+    }
+
+    let child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    {
+        let mut stdin = child.stdin.as_ref().unwrap();
+        for (index, entry) in entries.iter().enumerate() {
+            writeln!(stdin, "{}", picker_line(index, entry))?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_selection(
+        &String::from_utf8_lossy(&output.stdout),
+        entries.len(),
+    ))
+}
+
+/// `index<TAB>key<TAB>label`, with tabs and newlines in the label flattened
+fn picker_line(index: usize, entry: &PickerEntry) -> String {
+    let label = entry.label.replace(['\t', '\n', '\r'], " ");
+    format!("{}\t{}\t{}", index, entry.key, label)
+}
+
+/// Read the entry indices back from the lines fzf printed
+fn parse_selection(output: &str, len: usize) -> Vec<usize> {
+    output
+        .lines()
+        .filter_map(|line| line.split('\t').next()?.parse().ok())
+        .filter(|&index| index < len)
+        .collect()
+}
+
+/// Indices of the labels containing every whitespace-separated word of `query`, ignoring case
+fn filter(labels: &[String], query: &str) -> Vec<usize> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    labels
+        .iter()
+        .enumerate()
+        .filter(|(_, label)| {
+            let label = label.to_lowercase();
+            words.iter().all(|word| label.contains(word.as_str()))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Puts the terminal back into its normal state, even on early return
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stderr(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Full-screen selector with incremental filtering, drawn on stderr.
+/// With `multi`, Tab marks entries and Enter returns them in the order they were marked.
+fn pick_builtin(labels: &[String], multi: bool) -> Result<Vec<usize>> {
+    let _terminal = RawTerminal::enter()?;
+    let mut out = io::stderr();
+
+    let mut query = String::new();
+    let mut matches = filter(labels, &query);
+    let mut selected = 0usize;
+    let mut scroll = 0usize;
+    let mut marked: Vec<usize> = Vec::new();
+
+    loop {
+        let (width, height) = terminal::size()?;
+        let rows = (height as usize).saturating_sub(2).max(1);
+        if selected < scroll {
+            scroll = selected;
+        } else if selected >= scroll + rows {
+            scroll = selected + 1 - rows;
+        }
+
+        queue!(
+            out,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            Print(format!("> {}", query)),
+            cursor::MoveTo(0, 1),
+            Print(format!("  {}/{}", matches.len(), labels.len())),
+        )?;
+        for (row, &index) in matches.iter().skip(scroll).take(rows).enumerate() {
+            let label: String = labels[index]
+                .chars()
+                .take((width as usize).saturating_sub(2))
+                .collect();
+            let mark = if marked.contains(&index) { '*' } else { ' ' };
+            queue!(out, cursor::MoveTo(0, row as u16 + 2))?;
+            if scroll + row == selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(format!(">{}{}", mark, label)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(format!(" {}{}", mark, label)))?;
+            }
+        }
+        out.flush()?;
+
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        }) = event::read()?
+        else {
+            continue;
+        };
+
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Esc => return Ok(Vec::new()),
+            KeyCode::Char('c') | KeyCode::Char('g') if ctrl => return Ok(Vec::new()),
+            KeyCode::Enter => {
+                if !marked.is_empty() {
+                    return Ok(marked);
+                }
+                if let Some(&index) = matches.get(selected) {
+                    return Ok(vec![index]);
+                }
+            }
+            KeyCode::Tab if multi => {
+                if let Some(&index) = matches.get(selected) {
+                    match marked.iter().position(|&m| m == index) {
+                        Some(pos) => {
+                            marked.remove(pos);
+                        }
+                        None => marked.push(index),
+                    }
+                    selected = (selected + 1).min(matches.len().saturating_sub(1));
+                }
+            }
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Char('p') | KeyCode::Char('k') if ctrl => {
+                selected = selected.saturating_sub(1)
+            }
+            KeyCode::Down => selected = (selected + 1).min(matches.len().saturating_sub(1)),
+            KeyCode::Char('n') | KeyCode::Char('j') if ctrl => {
+                selected = (selected + 1).min(matches.len().saturating_sub(1))
+            }
+            KeyCode::PageUp => selected = selected.saturating_sub(rows),
+            KeyCode::PageDown => selected = (selected + rows).min(matches.len().saturating_sub(1)),
+            KeyCode::Backspace => {
+                query.pop();
+                matches = filter(labels, &query);
+                selected = 0;
+            }
+            KeyCode::Char('u') if ctrl => {
+                query.clear();
+                matches = filter(labels, &query);
+                selected = 0;
+            }
+            KeyCode::Char(c) if !ctrl => {
+                query.push(c);
+                matches = filter(labels, &query);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_matches_all_words_case_insensitively() {
+        let labels: Vec<String> = [
+            "Roads | Portishead",
+            "Glory Box | Portishead",
+            "Teardrop | Massive Attack",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        assert_eq!(filter(&labels, ""), [0, 1, 2]);
+        assert_eq!(filter(&labels, "portis"), [0, 1]);
+        assert_eq!(filter(&labels, "BOX portishead"), [1]);
+        assert!(filter(&labels, "radiohead").is_empty());
+    }
+
+    #[test]
+    fn test_selection_uses_hidden_index_column() {
+        let entry = PickerEntry {
+            key: "video:abc123".to_string(),
+            label: "Roads\t| Portishead".to_string(),
+        };
+        assert_eq!(
+            picker_line(4, &entry),
+            "4\tvideo:abc123\tRoads | Portishead"
+        );
+
+        // Identical labels still map back to distinct entries, in selection order
+        let output = "2\tvideo:b\tSame | Artist\n0\tvideo:a\tSame | Artist\n9\tvideo:z\tGone\n";
+        assert_eq!(parse_selection(output, 3), [2, 0]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub url: String,
+    pub title: String,
+    /// Title mpv shows for the entry instead of the one reported by yt-dlp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_title: Option<String>,
+    /// Playlist or album the entry was expanded from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist: Option<String>,
+}
+
+/// Playback queue owned by ytm, mirrored to a JSON file so it outlives mpv
+pub struct Queue {
+    path: PathBuf,
+    entries: Vec<QueueEntry>,
+}
+
+impl Queue {
+    /// Load the queue from `path`, starting empty if the file does not exist yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .with_context(|| format!("failed to parse queue file at {:?}", path))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read queue file at {:?}", path))
+            }
+        };
+        Ok(Self { path, entries })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let raw = serde_json::to_vec_pretty(&self.entries)?;
+        fs::write(&self.path, raw)
+            .with_context(|| format!("failed to write queue file at {:?}", self.path))
+    }
+
+    pub fn entries(&self) -> &[QueueEntry] {
+        &self.entries
+    }
+
+    pub fn push(&mut self, entry: QueueEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Replace the whole queue, e.g. when a fresh search result starts playing
+    pub fn replace(&mut self, entries: Vec<QueueEntry>) {
+        self.entries = entries;
+    }
+
+    /// Remove the entry at 1-based position `n`
+    pub fn remove(&mut self, n: usize) -> Result<QueueEntry> {
+        let index = self.index(n)?;
+        Ok(self.entries.remove(index))
+    }
+
+    /// Move the entry at 1-based position `from` so it ends up at position `to`
+    pub fn move_entry(&mut self, from: usize, to: usize) -> Result<()> {
+        let from = self.index(from)?;
+        let to = self.index(to)?;
+        let entry = self.entries.remove(from);
+        self.entries.insert(to, entry);
+        Ok(())
+    }
+
+    fn index(&self, n: usize) -> Result<usize> {
+        if n == 0 || n > self.entries.len() {
+            return Err(anyhow::anyhow!(
+                "No queue entry #{} (queue has {} entries)",
+                n,
+                self.entries.len()
+            ));
+        }
+        Ok(n - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(name: &str) -> QueueEntry {
+        QueueEntry {
+            url: format!("https://www.youtube.com/watch?v={name}"),
+            title: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn titles(queue: &Queue) -> Vec<&str> {
+        queue.entries().iter().map(|e| e.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_queue_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("queue.json");
+
+        let mut queue = Queue::load(&path).unwrap();
+        assert!(queue.entries().is_empty());
+
+        queue.push(entry("a"));
+        queue.push(entry("b"));
+        queue.save().unwrap();
+
+        let reloaded = Queue::load(&path).unwrap();
+        assert_eq!(reloaded.entries(), queue.entries());
+    }
+
+    #[test]
+    fn test_queue_remove_and_move() {
+        let temp_dir = tempdir().unwrap();
+        let mut queue = Queue::load(temp_dir.path().join("queue.json")).unwrap();
+        queue.replace(vec![entry("a"), entry("b"), entry("c"), entry("d")]);
+
+        queue.move_entry(1, 3).unwrap();
+        assert_eq!(titles(&queue), ["b", "c", "a", "d"]);
+
+        queue.move_entry(4, 1).unwrap();
+        assert_eq!(titles(&queue), ["d", "b", "c", "a"]);
+
+        let removed = queue.remove(2).unwrap();
+        assert_eq!(removed.title, "b");
+        assert_eq!(titles(&queue), ["d", "c", "a"]);
+
+        assert!(queue.remove(0).is_err());
+        assert!(queue.move_entry(1, 4).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Videos at least this long (in seconds) continue where they were left off without `--resume`
+pub const AUTO_RESUME_MIN_DURATION: f64 = 20.0 * 60.0;
+
+/// Positions this close to the start are not worth resuming from
+const MIN_POSITION: f64 = 10.0;
+
+/// Positions this close to the end count as finished
+const FINISHED_MARGIN: f64 = 30.0;
+
+/// How often a running player's position is saved, so a killed player loses little
+const SAVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Whether playback continues from a saved position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResumePolicy {
+    /// Only videos of at least [`AUTO_RESUME_MIN_DURATION`]
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedPosition {
+    /// Seconds into the video
+    pub position: f64,
+    pub duration: Option<f64>,
+    /// When it was saved, in seconds since the Unix epoch
+    pub saved_at: i64,
+}
+
+impl SavedPosition {
+    /// Where to start playing under `policy`, if not from the beginning
+    pub fn start(&self, policy: ResumePolicy) -> Option<f64> {
+        let resume = match policy {
+            ResumePolicy::Always => true,
+            ResumePolicy::Never => false,
+            ResumePolicy::Auto => self
+                .duration
+                .is_some_and(|duration| duration >= AUTO_RESUME_MIN_DURATION),
+        };
+        resume.then_some(self.position)
+    }
+}
+
+/// Last known position per video id, mirrored to a JSON file
+pub struct Positions {
+    path: PathBuf,
+    positions: HashMap<String, SavedPosition>,
+}
+
+impl Positions {
+    /// Load the positions from `path`. A missing file starts empty, and so does an unreadable
+    /// one, with a warning: losing resume points is better than refusing to play.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let positions = match fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw).unwrap_or_else(|err| {
+                eprintln!("Ignoring broken positions file at {:?}: {}", path, err);
+                HashMap::new()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => {
+                eprintln!("Ignoring unreadable positions file at {:?}: {}", path, err);
+                HashMap::new()
+            }
+        };
+        Self { path, positions }
+    }
+
+    /// Write the positions to a temporary file first and move it into place, so a writer
+    /// killed part way never leaves a truncated file behind
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let raw = serde_json::to_vec_pretty(&self.positions)?;
+        // One per process, as the watcher and `ytm stop` may save at the same time
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(format!(".{}.tmp", std::process::id()));
+        let temp_path = self.path.with_file_name(temp_name);
+        fs::write(&temp_path, raw)
+            .with_context(|| format!("failed to write positions file at {:?}", temp_path))?;
+        fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("failed to replace positions file at {:?}", self.path))
+    }
+
+    pub fn get(&self, video_id: &str) -> Option<&SavedPosition> {
+        self.positions.get(video_id)
+    }
+
+    /// Remember how far a video was played, or forget it when it was barely started or
+    /// played to the end
+    pub fn record(&mut self, played: &PlayedPosition, saved_at: i64) {
+        let finished = played
+            .duration
+            .is_some_and(|duration| played.position >= duration - FINISHED_MARGIN);
+        if played.position < MIN_POSITION || finished {
+            self.positions.remove(&played.video_id);
+        } else {
+            self.positions.insert(
+                played.video_id.clone(),
+                SavedPosition {
+                    position: played.position,
+                    duration: played.duration,
+                    saved_at,
+                },
+            );
+        }
+    }
+}
+
+/// Position reached in a video
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayedPosition {
+    pub video_id: String,
+    pub position: f64,
+    pub duration: Option<f64>,
+}
+
+/// Follows the position in the video a player is playing and says when to save it
+#[derive(Default)]
+pub struct PositionTracker {
+    video_id: Option<String>,
+    duration: Option<f64>,
+    position: Option<f64>,
+    last_saved: Option<Instant>,
+}
+
+impl PositionTracker {
+    /// Follow a new video, returning the position reached in the previous one
+    pub fn start(&mut self, video_id: Option<String>) -> Option<PlayedPosition> {
+        let previous = self.finish();
+        self.video_id = video_id;
+        self.last_saved = Some(Instant::now());
+        previous
+    }
+
+    pub fn set_duration(&mut self, duration: Option<f64>) {
+        self.duration = duration;
+    }
+
+    /// Note the current position, returning it when a periodic save is due
+    pub fn update(&mut self, position: f64) -> Option<PlayedPosition> {
+        self.position = Some(position);
+        if self
+            .last_saved
+            .is_some_and(|at| at.elapsed() < SAVE_INTERVAL)
+        {
+            return None;
+        }
+        self.last_saved = Some(Instant::now());
+        self.played()
+    }
+
+    /// Stop following the video, returning the position reached in it
+    pub fn finish(&mut self) -> Option<PlayedPosition> {
+        let played = self.played();
+        *self = Self::default();
+        played
+    }
+
+    fn played(&self) -> Option<PlayedPosition> {
+        Some(PlayedPosition {
+            video_id: self.video_id.clone()?,
+            position: self.position?,
+            duration: self.duration,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn played(position: f64, duration: f64) -> PlayedPosition {
+        PlayedPosition {
+            video_id: "set123".to_string(),
+            position,
+            duration: Some(duration),
+        }
+    }
+
+    #[test]
+    fn test_positions_forget_finished_videos() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("positions.json");
+
+        let mut positions = Positions::load(&path);
+        positions.record(&played(1800.0, 3600.0), 1);
+        positions.save().unwrap();
+
+        let mut positions = Positions::load(&path);
+        let saved = *positions.get("set123").unwrap();
+        assert_eq!(saved.position, 1800.0);
+        assert_eq!(saved.start(ResumePolicy::Auto), Some(1800.0));
+        assert_eq!(saved.start(ResumePolicy::Never), None);
+
+        positions.record(&played(3590.0, 3600.0), 2);
+        assert!(positions.get("set123").is_none());
+        positions.record(&played(5.0, 3600.0), 3);
+        assert!(positions.get("set123").is_none());
+    }
+
+    #[test]
+    fn test_truncated_positions_file_loads_empty() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("positions.json");
+        fs::write(&path, "{\"set123\": {\"posit").unwrap();
+
+        let mut positions = Positions::load(&path);
+        assert!(positions.get("set123").is_none());
+        positions.record(&played(1800.0, 3600.0), 1);
+        positions.save().unwrap();
+        assert!(Positions::load(&path).get("set123").is_some());
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_short_videos_resume_only_when_asked() {
+        let saved = SavedPosition {
+            position: 95.0,
+            duration: Some(240.0),
+            saved_at: 0,
+        };
+        assert_eq!(saved.start(ResumePolicy::Auto), None);
+        assert_eq!(saved.start(ResumePolicy::Always), Some(95.0));
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs::{self, DirBuilder};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{Context, Result};
+
+const DEFAULT_SESSION: &str = "default";
+
+static CURRENT: OnceLock<Session> = OnceLock::new();
+
+/// A named player instance and the IPC socket it is controlled through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    name: String,
+    socket: PathBuf,
+    /// Whether the socket path was given by the user rather than placed in [`socket_dir`]
+    custom_socket: bool,
+}
+
+impl Session {
+    /// Resolve a session from an optional name and an optional explicit socket path
+    pub fn new(name: Option<String>, socket: Option<PathBuf>) -> Result<Self> {
+        let name = name.unwrap_or_else(|| DEFAULT_SESSION.to_string());
+        if name.is_empty() || name.contains(['/', '\0']) || name.starts_with('.') {
+            return Err(anyhow::anyhow!("Invalid session name '{}'", name));
+        }
+
+        let custom_socket = socket.is_some();
+        let socket = match socket {
+            Some(path) => path,
+            None => socket_dir().join(format!("{name}.sock")),
+        };
+        Ok(Self {
+            name,
+            socket,
+            custom_socket,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_SESSION
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Create the socket's directory. ytm's own directory is kept private to the current
+    /// user; the directory of a user-supplied path is only created if missing, never changed.
+    pub fn ensure_socket_dir(&self) -> Result<()> {
+        match self.socket.parent() {
+            Some(dir) if dir.as_os_str().is_empty() => Ok(()),
+            Some(dir) if self.custom_socket => fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create socket dir at {:?}", dir)),
+            Some(dir) => ensure_private_dir(dir),
+            None => Ok(()),
+        }
+    }
+
+    /// Report a socket directory ytm could not use, without creating or changing anything
+    pub fn check_socket_dir(&self) -> Result<()> {
+        let Some(dir) = self
+            .socket
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        else {
+            return Ok(());
+        };
+        let meta = match fs::metadata(dir) {
+            Ok(meta) => meta,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read socket dir {:?}", dir))
+            }
+        };
+        if !meta.is_dir() {
+            return Err(anyhow::anyhow!("Socket dir {:?} is not a directory", dir));
+        }
+        if !self.custom_socket {
+            check_owner(dir, &meta)?;
+        }
+        Ok(())
+    }
+}
+
+/// Select the session used by every command in this process. Call once, before any IPC.
+pub fn init(name: Option<String>, socket: Option<PathBuf>) -> Result<()> {
+    let session = Session::new(name, socket)?;
+    CURRENT
+        .set(session)
+        .map_err(|_| anyhow::anyhow!("session already initialized"))
+}
+
+/// The session selected with [`init`], or the default one
+pub fn current() -> &'static Session {
+    CURRENT.get_or_init(|| Session::new(None, None).expect("default session name is valid"))
+}
+
+/// `$XDG_RUNTIME_DIR/ytm`, or a per-user directory under the temp dir where that is unset
+fn socket_dir() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("ytm"),
+        None => std::env::temp_dir().join(format!("ytm-{}", nix::unistd::getuid())),
+    }
+}
+
+/// Create `dir` with mode 0700, refusing to use an existing one another user could tamper with
+fn ensure_private_dir(dir: &Path) -> Result<()> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("failed to create socket dir at {:?}", dir))?;
+
+    let meta = fs::metadata(dir)?;
+    check_owner(dir, &meta)?;
+    if meta.permissions().mode() & 0o077 != 0 {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+fn check_owner(dir: &Path, meta: &fs::Metadata) -> Result<()> {
+    if meta.uid() != nix::unistd::getuid().as_raw() {
+        return Err(anyhow::anyhow!(
+            "Socket dir {:?} is owned by another user; pass --socket to use a different path",
+            dir
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_session_paths() {
+        let default = Session::new(None, None).unwrap();
+        assert!(default.is_default());
+        assert_eq!(default.socket().file_name().unwrap(), "default.sock");
+
+        let work = Session::new(Some("work".to_string()), None).unwrap();
+        assert_eq!(work.socket().file_name().unwrap(), "work.sock");
+        assert_eq!(work.socket().parent(), default.socket().parent());
+
+        let custom = Session::new(None, Some(PathBuf::from("/run/custom.sock"))).unwrap();
+        assert_eq!(custom.socket(), Path::new("/run/custom.sock"));
+
+        assert!(Session::new(Some("../evil".to_string()), None).is_err());
+        assert!(Session::new(Some(String::new()), None).is_err());
+    }
+
+    #[test]
+    fn test_socket_dir_is_private() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("ytm");
+        fs::create_dir(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+
+        ensure_private_dir(&dir).unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    #[test]
+    fn test_custom_socket_dir_is_left_alone() {
+        let temp_dir = tempdir().unwrap();
+        let dir = temp_dir.path().join("home");
+        fs::create_dir(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let session = Session::new(None, Some(dir.join("my.sock"))).unwrap();
+        session.check_socket_dir().unwrap();
+        session.ensure_socket_dir().unwrap();
+        let mode = fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+
+        let nested = Session::new(None, Some(dir.join("new/my.sock"))).unwrap();
+        nested.ensure_socket_dir().unwrap();
+        assert!(dir.join("new").is_dir());
+    }
+}